@@ -49,7 +49,7 @@ fn to_edge(field: &syn::Field) -> Option<Edge> {
     let mut split = ident.split("_to_");
     let _from_collection = split.next()?;
     let _to_collection = split.next()?;
-    if !split.next().is_none() {
+    if split.next().is_some() {
         return None;
     }
     let segment = if let syn::Ty::Path(_, ref path) = field.ty {
@@ -59,7 +59,7 @@ fn to_edge(field: &syn::Field) -> Option<Edge> {
     }?;
     let (from_ty, to_ty) = if let syn::PathParameters::AngleBracketed(ref data) = segment.parameters
     {
-        match (data.types.get(0), data.types.get(1), data.types.get(2)) {
+        match (data.types.first(), data.types.get(1), data.types.get(2)) {
             (Some(from_ty), Some(to_ty), None) => Some((from_ty, to_ty)),
             _ => None,
         }
@@ -87,7 +87,7 @@ fn make_edge_to_get_corresponding<'a>(
             quote! {
                 impl GetCorresponding<#to> for IdxSet<#from> {
                     fn get_corresponding(&self, pt_objects: &#name) -> IdxSet<#to> {
-                        pt_objects.#ident.get_corresponding_forward(self)
+                        pt_objects.#ident.get_corresponding_forward(pt_objects, self)
                     }
                 }
             },
@@ -97,7 +97,7 @@ fn make_edge_to_get_corresponding<'a>(
             quote! {
                 impl GetCorresponding<#from> for IdxSet<#to> {
                     fn get_corresponding(&self, pt_objects: &#name) -> IdxSet<#from> {
-                        pt_objects.#ident.get_corresponding_backward(self)
+                        pt_objects.#ident.get_corresponding_backward(pt_objects, self)
                     }
                 }
             },
@@ -106,8 +106,7 @@ fn make_edge_to_get_corresponding<'a>(
     res
 }
 
-fn floyd_warshall<'a>(edges: &'a [Edge]) -> HashMap<(&'a Node, &'a Node), &'a Node> {
-    use std::f64::INFINITY;
+fn floyd_warshall(edges: &[Edge]) -> HashMap<(&Node, &Node), &Node> {
     let mut v = HashSet::<&Node>::default();
     let mut dist = HashMap::<(&Node, &Node), f64>::default();
     let mut next = HashMap::default();
@@ -132,7 +131,7 @@ fn floyd_warshall<'a>(edges: &'a [Edge]) -> HashMap<(&'a Node, &'a Node), &'a No
                     Some(d) => *d,
                     None => continue,
                 };
-                let dist_ij = dist.entry((i, j)).or_insert(INFINITY);
+                let dist_ij = dist.entry((i, j)).or_insert(f64::INFINITY);
                 if *dist_ij > dist_ik + dist_kj {
                     *dist_ij = dist_ik + dist_kj;
                     let next_ik = next[&(i, k)];
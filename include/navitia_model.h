@@ -0,0 +1,24 @@
+#ifndef NAVITIA_MODEL_H
+#define NAVITIA_MODEL_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct PtObjects PtObjects;
+
+/* Returns NULL on failure. The model must be released with navitia_model_free. */
+PtObjects *navitia_model_read_ntfs(const char *path);
+
+/* The following functions return 0 on success and -1 on failure. */
+int navitia_model_write_ntfs(const PtObjects *model, const char *path);
+int navitia_model_sanitize(PtObjects *model);
+int navitia_model_add_prefix(PtObjects *model, const char *prefix, const char *separator);
+
+void navitia_model_free(PtObjects *model);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
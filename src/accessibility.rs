@@ -0,0 +1,83 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path;
+use csv;
+use serde_json;
+
+use error::{csv_error, io_error, json_error, Result};
+use objects::Availability;
+use Collections;
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct AccessibilityStats {
+    pub network_id: String,
+    pub physical_mode_id: String,
+    pub nb_vehicle_journeys: usize,
+    pub nb_known: usize,
+    pub nb_accessible: usize,
+    pub known_ratio: f64,
+    pub accessible_ratio: f64,
+}
+
+fn ratio(count: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.
+    } else {
+        count as f64 / total as f64
+    }
+}
+
+// Trips whose route or line cannot be found are not counted. Ratios are
+// given over all the trips of the group.
+pub fn compute(collections: &Collections) -> Vec<AccessibilityStats> {
+    let mut groups = BTreeMap::new();
+    for (_, vj) in collections.vehicle_journeys.iter() {
+        let network_id = match collections
+            .routes
+            .get(&vj.route_id)
+            .and_then(|r| collections.lines.get(&r.line_id))
+        {
+            Some(line) => line.network_id.clone(),
+            None => continue,
+        };
+        let stats = groups
+            .entry((network_id.clone(), vj.physical_mode_id.clone()))
+            .or_insert_with(|| AccessibilityStats {
+                network_id,
+                physical_mode_id: vj.physical_mode_id.clone(),
+                ..AccessibilityStats::default()
+            });
+        stats.nb_vehicle_journeys += 1;
+        match vj.wheelchair_accessible {
+            Some(Availability::Available) => {
+                stats.nb_known += 1;
+                stats.nb_accessible += 1;
+            }
+            Some(Availability::NotAvailable) => stats.nb_known += 1,
+            Some(Availability::InformationNotAvailable) | None => {}
+        }
+    }
+    groups
+        .into_values()
+        .map(|mut stats| {
+            stats.known_ratio = ratio(stats.nb_known, stats.nb_vehicle_journeys);
+            stats.accessible_ratio = ratio(stats.nb_accessible, stats.nb_vehicle_journeys);
+            stats
+        })
+        .collect()
+}
+
+pub fn write_csv<P: AsRef<path::Path>>(path: P, stats: &[AccessibilityStats]) -> Result<()> {
+    let path = path.as_ref();
+    let mut wtr = csv::Writer::from_path(path).map_err(|e| csv_error(path, e))?;
+    for s in stats {
+        wtr.serialize(s).map_err(|e| csv_error(path, e))?;
+    }
+    wtr.flush().map_err(|e| io_error(path, e))
+}
+
+pub fn write_json<P: AsRef<path::Path>>(path: P, stats: &[AccessibilityStats]) -> Result<()> {
+    let path = path.as_ref();
+    let file = fs::File::create(path).map_err(|e| io_error(path, e))?;
+    serde_json::to_writer_pretty(file, stats).map_err(|e| json_error(path, e))
+}
@@ -0,0 +1,537 @@
+use std::collections::HashSet;
+use std::fs;
+use std::mem;
+use std::path;
+use csv;
+use serde_json;
+
+use collection::Collection;
+use error::{self, csv_error, io_error, json_error};
+use objects::{is_valid_color, Network, ObjectCode, ObjectType};
+use Collections;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PropertyRule {
+    pub object_type: ObjectType,
+    pub object_id: String,
+    pub property_name: String,
+    pub property_old_value: Option<String>,
+    pub property_value: String,
+}
+
+// The object is found by its id, or when there is none, by an existing
+// code; every object of the type having that code gets the new one.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CodeRule {
+    pub object_type: ObjectType,
+    #[serde(default)]
+    pub object_id: Option<String>,
+    #[serde(default)]
+    pub matching_system: Option<String>,
+    #[serde(default)]
+    pub matching_code: Option<String>,
+    pub object_system: String,
+    pub object_code: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct NetworkConsolidation {
+    pub network: Network,
+    pub grouped_from: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct NetworkConsolidationRules {
+    networks: Vec<NetworkConsolidation>,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct Report {
+    pub unmatched_networks: Vec<String>,
+    pub invalid_network_consolidations: Vec<String>,
+    pub unmatched_codes: Vec<CodeRule>,
+    pub unmatched_properties: Vec<PropertyRule>,
+    pub invalid_properties: Vec<PropertyRule>,
+}
+
+enum PropertyError {
+    Unmatched,
+    Invalid,
+}
+
+fn consolidate_network(
+    collections: &mut Collections,
+    consolidation: NetworkConsolidation,
+    report: &mut Report,
+) {
+    let new_id = consolidation.network.id.clone();
+    if collections.networks.get(&new_id).is_some()
+        && !consolidation.grouped_from.contains(&new_id)
+    {
+        report.invalid_network_consolidations.push(new_id);
+        return;
+    }
+    let mut grouped_from = HashSet::new();
+    for id in consolidation.grouped_from {
+        if collections.networks.get(&id).is_some() {
+            grouped_from.insert(id);
+        } else {
+            report.unmatched_networks.push(id);
+        }
+    }
+    if grouped_from.is_empty() {
+        report.invalid_network_consolidations.push(new_id);
+        return;
+    }
+
+    let mut networks: Vec<_> = mem::take(&mut collections.networks)
+        .into_vec()
+        .into_iter()
+        .filter(|n| !grouped_from.contains(&n.id))
+        .collect();
+    networks.push(consolidation.network);
+    collections.networks = Collection::from_vec(networks);
+
+    let mut lines = mem::take(&mut collections.lines).into_vec();
+    for line in &mut lines {
+        if grouped_from.contains(&line.network_id) {
+            line.network_id = new_id.clone();
+        }
+    }
+    collections.lines = Collection::from_vec(lines);
+
+    let mut seen = HashSet::new();
+    let object_codes = mem::take(&mut collections.object_codes);
+    for mut code in object_codes {
+        if code.object_type == ObjectType::Network && grouped_from.contains(&code.object_id) {
+            code.object_id = new_id.clone();
+        }
+        if seen.insert(code.clone()) {
+            collections.object_codes.push(code);
+        }
+    }
+
+    let mut seen = HashSet::new();
+    let perimeters = mem::take(&mut collections.ticket_use_perimeters);
+    for mut perimeter in perimeters {
+        if perimeter.object_type == ObjectType::Network
+            && grouped_from.contains(&perimeter.object_id)
+        {
+            perimeter.object_id = new_id.clone();
+        }
+        if seen.insert(perimeter.clone()) {
+            collections.ticket_use_perimeters.push(perimeter);
+        }
+    }
+}
+
+fn apply_network_consolidation(
+    collections: &mut Collections,
+    path: &path::Path,
+    report: &mut Report,
+) -> error::Result<()> {
+    let file = fs::File::open(path).map_err(|e| io_error(path, e))?;
+    let rules: NetworkConsolidationRules =
+        serde_json::from_reader(file).map_err(|e| json_error(path, e))?;
+    for consolidation in rules.networks {
+        consolidate_network(collections, consolidation, report);
+    }
+    Ok(())
+}
+
+fn matching_objects(collections: &Collections, rule: &CodeRule) -> Vec<String> {
+    match (&rule.object_id, &rule.matching_system, &rule.matching_code) {
+        (Some(id), _, _) => {
+            if collections.object_exists(rule.object_type, id) {
+                vec![id.clone()]
+            } else {
+                vec![]
+            }
+        }
+        (None, Some(system), Some(code)) => {
+            let mut ids = vec![];
+            for c in &collections.object_codes {
+                if c.object_type == rule.object_type
+                    && c.object_system == *system
+                    && c.object_code == *code
+                    && collections.object_exists(c.object_type, &c.object_id)
+                    && !ids.contains(&c.object_id)
+                {
+                    ids.push(c.object_id.clone());
+                }
+            }
+            ids
+        }
+        _ => vec![],
+    }
+}
+
+fn apply_complementary_codes(
+    collections: &mut Collections,
+    path: &path::Path,
+    report: &mut Report,
+) -> error::Result<()> {
+    let mut seen: HashSet<ObjectCode> = collections.object_codes.iter().cloned().collect();
+    let mut rdr = csv::Reader::from_path(path).map_err(|e| csv_error(path, e))?;
+    for rule in rdr.deserialize() {
+        let rule: CodeRule = rule.map_err(|e| csv_error(path, e))?;
+        let object_ids = matching_objects(collections, &rule);
+        if object_ids.is_empty() {
+            report.unmatched_codes.push(rule);
+            continue;
+        }
+        for object_id in object_ids {
+            let code = ObjectCode {
+                object_type: rule.object_type,
+                object_id,
+                object_system: rule.object_system.clone(),
+                object_code: rule.object_code.clone(),
+            };
+            if seen.insert(code.clone()) {
+                collections.object_codes.push(code);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn matches_old_value(current: &str, rule: &PropertyRule) -> bool {
+    match rule.property_old_value {
+        None => true,
+        Some(ref old) => old == "*" || old == current,
+    }
+}
+
+fn set_property(property: &mut String, rule: &PropertyRule) -> Result<(), PropertyError> {
+    if !matches_old_value(property, rule) {
+        return Err(PropertyError::Unmatched);
+    }
+    *property = rule.property_value.clone();
+    Ok(())
+}
+
+fn set_opt_property(
+    property: &mut Option<String>,
+    rule: &PropertyRule,
+) -> Result<(), PropertyError> {
+    if !matches_old_value(property.as_ref().map_or("", |p| p.as_str()), rule) {
+        return Err(PropertyError::Unmatched);
+    }
+    *property = Some(rule.property_value.clone());
+    Ok(())
+}
+
+fn apply_property(collections: &mut Collections, rule: &PropertyRule) -> Result<(), PropertyError> {
+    let id = rule.object_id.as_str();
+    match (rule.object_type, rule.property_name.as_str()) {
+        (ObjectType::Network, "network_name") => {
+            let network = collections
+                .networks
+                .get_mut(id)
+                .ok_or(PropertyError::Unmatched)?;
+            set_property(&mut network.name, rule)
+        }
+        (ObjectType::Line, "line_name") => {
+            let line = collections
+                .lines
+                .get_mut(id)
+                .ok_or(PropertyError::Unmatched)?;
+            set_property(&mut line.name, rule)
+        }
+        (ObjectType::Line, "line_color") | (ObjectType::Line, "line_text_color") => {
+            if !is_valid_color(&rule.property_value) {
+                return Err(PropertyError::Invalid);
+            }
+            let line = collections
+                .lines
+                .get_mut(id)
+                .ok_or(PropertyError::Unmatched)?;
+            if rule.property_name == "line_color" {
+                set_opt_property(&mut line.color, rule)
+            } else {
+                set_opt_property(&mut line.text_color, rule)
+            }
+        }
+        (ObjectType::Line, "commercial_mode_id") => {
+            if collections
+                .commercial_modes
+                .get(&rule.property_value)
+                .is_none()
+            {
+                return Err(PropertyError::Invalid);
+            }
+            let line = collections
+                .lines
+                .get_mut(id)
+                .ok_or(PropertyError::Unmatched)?;
+            set_property(&mut line.commercial_mode_id, rule)
+        }
+        (ObjectType::Route, "route_name") => {
+            let route = collections
+                .routes
+                .get_mut(id)
+                .ok_or(PropertyError::Unmatched)?;
+            set_property(&mut route.name, rule)
+        }
+        _ => Err(PropertyError::Invalid),
+    }
+}
+
+fn apply_properties(
+    collections: &mut Collections,
+    path: &path::Path,
+    report: &mut Report,
+) -> error::Result<()> {
+    let mut rdr = csv::Reader::from_path(path).map_err(|e| csv_error(path, e))?;
+    for rule in rdr.deserialize() {
+        let rule: PropertyRule = rule.map_err(|e| csv_error(path, e))?;
+        match apply_property(collections, &rule) {
+            Ok(()) => {}
+            Err(PropertyError::Unmatched) => report.unmatched_properties.push(rule),
+            Err(PropertyError::Invalid) => report.invalid_properties.push(rule),
+        }
+    }
+    Ok(())
+}
+
+pub fn apply_rules<P: AsRef<path::Path>>(
+    collections: &mut Collections,
+    complementary_code_rules_files: &[P],
+    property_rules_files: &[P],
+    networks_consolidation_file: Option<P>,
+) -> error::Result<Report> {
+    let mut report = Report::default();
+    if let Some(path) = networks_consolidation_file {
+        apply_network_consolidation(collections, path.as_ref(), &mut report)?;
+    }
+    for path in complementary_code_rules_files {
+        apply_complementary_codes(collections, path.as_ref(), &mut report)?;
+    }
+    for path in property_rules_files {
+        apply_properties(collections, path.as_ref(), &mut report)?;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builder::ModelBuilder;
+    use objects::{PerimeterAction, TicketUsePerimeter};
+
+    fn code(object_type: ObjectType, object_id: &str, system: &str, value: &str) -> ObjectCode {
+        ObjectCode {
+            object_type,
+            object_id: object_id.to_string(),
+            object_system: system.to_string(),
+            object_code: value.to_string(),
+        }
+    }
+
+    fn codes_rule(object_id: Option<&str>, matching: Option<(&str, &str)>) -> CodeRule {
+        CodeRule {
+            object_type: ObjectType::Line,
+            object_id: object_id.map(String::from),
+            matching_system: matching.map(|(system, _)| system.to_string()),
+            matching_code: matching.map(|(_, code)| code.to_string()),
+            object_system: "new".to_string(),
+            object_code: "X".to_string(),
+        }
+    }
+
+    fn property_rule(
+        object_type: ObjectType,
+        object_id: &str,
+        property_name: &str,
+        property_old_value: Option<&str>,
+        property_value: &str,
+    ) -> PropertyRule {
+        PropertyRule {
+            object_type,
+            object_id: object_id.to_string(),
+            property_name: property_name.to_string(),
+            property_old_value: property_old_value.map(String::from),
+            property_value: property_value.to_string(),
+        }
+    }
+
+    fn consolidation(id: &str, grouped_from: &[&str]) -> NetworkConsolidation {
+        NetworkConsolidation {
+            network: Network {
+                id: id.to_string(),
+                name: id.to_string(),
+                timezone: "Europe/Paris".to_string(),
+            },
+            grouped_from: grouped_from.iter().map(|id| id.to_string()).collect(),
+        }
+    }
+
+    fn collections() -> Collections {
+        let mut collections = ModelBuilder::new()
+            .network("n1")
+            .line("l1")
+            .route("r1")
+            .line("l2")
+            .network("n2")
+            .line("l3")
+            .build()
+            .into_collections();
+        collections.object_codes = vec![
+            code(ObjectType::Line, "l1", "ext", "A"),
+            code(ObjectType::Line, "l2", "ext", "A"),
+            code(ObjectType::Line, "l2", "other", "A"),
+            code(ObjectType::Route, "r1", "ext", "A"),
+            code(ObjectType::Line, "unknown", "ext", "A"),
+            code(ObjectType::Network, "n1", "ext", "N"),
+        ];
+        collections
+    }
+
+    #[test]
+    fn codes_match_by_id() {
+        let c = collections();
+        assert_eq!(matching_objects(&c, &codes_rule(Some("l3"), None)), ["l3"]);
+        assert!(matching_objects(&c, &codes_rule(Some("unknown"), None)).is_empty());
+        // The id takes precedence over the matching code.
+        let rule = codes_rule(Some("l3"), Some(("ext", "A")));
+        assert_eq!(matching_objects(&c, &rule), ["l3"]);
+    }
+
+    #[test]
+    fn codes_match_through_existing_codes() {
+        let c = collections();
+        // Only existing objects of the rule's type, each once.
+        let rule = codes_rule(None, Some(("ext", "A")));
+        assert_eq!(matching_objects(&c, &rule), ["l1", "l2"]);
+        assert!(matching_objects(&c, &codes_rule(None, Some(("ext", "B")))).is_empty());
+        // A matching system without a code matches nothing.
+        let mut rule = codes_rule(None, Some(("ext", "A")));
+        rule.matching_code = None;
+        assert!(matching_objects(&c, &rule).is_empty());
+    }
+
+    #[test]
+    fn complementary_codes_are_added_once_and_unmatched_rules_reported() {
+        let dir = std::env::temp_dir().join(format!("navitia_model_codes_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("codes.csv");
+        fs::write(
+            &path,
+            "object_type,object_id,matching_system,matching_code,object_system,object_code\n\
+             line,,ext,A,new,X\n\
+             line,l1,,,new,X\n\
+             network,n3,,,new,Y\n",
+        )
+        .unwrap();
+        let mut c = collections();
+        let report = apply_rules(&mut c, &[&path], &[], None);
+        fs::remove_dir_all(&dir).unwrap();
+        let report = report.unwrap();
+        let added: Vec<_> = c.object_codes.iter().filter(|c| c.object_system == "new").collect();
+        assert_eq!(
+            added,
+            [
+                &code(ObjectType::Line, "l1", "new", "X"),
+                &code(ObjectType::Line, "l2", "new", "X"),
+            ]
+        );
+        assert_eq!(report.unmatched_codes.len(), 1);
+        assert_eq!(report.unmatched_codes[0].object_id, Some("n3".to_string()));
+    }
+
+    #[test]
+    fn properties_match_on_their_old_value() {
+        let mut c = collections();
+        let rule = |old: Option<&str>, new: &str| {
+            property_rule(ObjectType::Line, "l1", "line_name", old, new)
+        };
+        assert!(apply_property(&mut c, &rule(None, "A")).is_ok());
+        assert!(apply_property(&mut c, &rule(Some("A"), "B")).is_ok());
+        assert!(matches!(
+            apply_property(&mut c, &rule(Some("A"), "C")),
+            Err(PropertyError::Unmatched)
+        ));
+        assert!(apply_property(&mut c, &rule(Some("*"), "D")).is_ok());
+        assert_eq!(c.lines.get("l1").unwrap().name, "D");
+        // A missing optional value matches an empty old value.
+        let color = |old: Option<&str>| {
+            property_rule(ObjectType::Line, "l1", "line_color", old, "FF0000")
+        };
+        assert!(matches!(
+            apply_property(&mut c, &color(Some("00FF00"))),
+            Err(PropertyError::Unmatched)
+        ));
+        assert!(apply_property(&mut c, &color(Some(""))).is_ok());
+        assert_eq!(c.lines.get("l1").unwrap().color, Some("FF0000".to_string()));
+    }
+
+    #[test]
+    fn invalid_properties_are_not_applied() {
+        let mut c = collections();
+        let invalid = [
+            property_rule(ObjectType::Line, "l1", "line_color", None, "#FF0000"),
+            property_rule(ObjectType::Line, "l1", "commercial_mode_id", None, "Tram"),
+            property_rule(ObjectType::Line, "l1", "unknown", None, "A"),
+            property_rule(ObjectType::VehicleJourney, "v1", "route_name", None, "A"),
+        ];
+        for rule in &invalid {
+            assert!(matches!(
+                apply_property(&mut c, rule),
+                Err(PropertyError::Invalid)
+            ));
+        }
+        let unknown_line = property_rule(ObjectType::Line, "unknown", "line_name", None, "A");
+        assert!(matches!(
+            apply_property(&mut c, &unknown_line),
+            Err(PropertyError::Unmatched)
+        ));
+        let line = c.lines.get("l1").unwrap();
+        assert_eq!(line.color, None);
+        assert_eq!(line.commercial_mode_id, "Bus");
+    }
+
+    #[test]
+    fn consolidation_remaps_references_to_grouped_networks() {
+        let mut c = collections();
+        c.object_codes.push(code(ObjectType::Network, "n2", "ext", "N"));
+        let perimeter = |network_id: &str| TicketUsePerimeter {
+            ticket_use_id: "u1".to_string(),
+            object_type: ObjectType::Network,
+            object_id: network_id.to_string(),
+            perimeter_action: PerimeterAction::Included,
+        };
+        c.ticket_use_perimeters = vec![perimeter("n1"), perimeter("n2")];
+        let mut report = Report::default();
+        consolidate_network(&mut c, consolidation("brand", &["n1", "n2", "n9"]), &mut report);
+        assert_eq!(report.unmatched_networks, ["n9"]);
+        assert!(report.invalid_network_consolidations.is_empty());
+        let networks: Vec<_> = c.networks.iter().map(|(_, n)| n.id.as_str()).collect();
+        assert_eq!(networks, ["brand"]);
+        assert!(c.lines.iter().all(|(_, l)| l.network_id == "brand"));
+        let network_codes: Vec<_> = c
+            .object_codes
+            .iter()
+            .filter(|c| c.object_type == ObjectType::Network)
+            .collect();
+        assert_eq!(network_codes, [&code(ObjectType::Network, "brand", "ext", "N")]);
+        assert_eq!(c.ticket_use_perimeters, [perimeter("brand")]);
+    }
+
+    #[test]
+    fn invalid_consolidations_change_nothing() {
+        let mut c = collections();
+        let mut report = Report::default();
+        // The new id is an existing network that is not grouped.
+        consolidate_network(&mut c, consolidation("n2", &["n1"]), &mut report);
+        // None of the grouped networks exists.
+        consolidate_network(&mut c, consolidation("brand", &["n9"]), &mut report);
+        assert_eq!(report.invalid_network_consolidations, ["n2", "brand"]);
+        assert_eq!(report.unmatched_networks, ["n9"]);
+        assert_eq!(c.networks.len(), 2);
+        assert_eq!(c.lines.get("l1").unwrap().network_id, "n1");
+        // An existing network can be kept as the consolidated one.
+        consolidate_network(&mut c, consolidation("n2", &["n1", "n2"]), &mut report);
+        assert_eq!(c.networks.len(), 1);
+        assert_eq!(c.lines.get("l1").unwrap().network_id, "n2");
+    }
+}
@@ -0,0 +1,253 @@
+extern crate navitia_model;
+extern crate serde_json;
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::process;
+
+use navitia_model::apply_rules;
+use navitia_model::merge::{self, MergePolicy};
+use navitia_model::pipeline;
+use navitia_model::prefix::{self, PrefixConfiguration};
+use navitia_model::progress::NoProgress;
+use navitia_model::sanitize::SanitizeConfig;
+use navitia_model::{ntfs, quality, stats, Collections, PtObjects};
+
+const USAGE: &str = "usage: navitia-model <command> [options]
+
+commands:
+    ntfs2ntfs <input> <output>
+        read, sanitize and write an NTFS
+    merge <input> <input> <output> [--on-conflict fail|keep-first|prefix:<p>]
+        merge two NTFS into one
+    apply-rules <input> <output> [--codes <file>]... [--properties <file>]...
+                [--networks-consolidation <file>]
+        apply complementary codes, property rules and network consolidation
+    validate <input>
+        check references and print what sanitizing would remove
+    stats <input> [--json <file>] [--csv <dir>]
+        print statistics per network and line
+    quality <input> [--json <file>] [--csv <file>]
+        report data quality issues, exits with 1 if any is an error
+    pipeline <config>
+        run the steps described in a JSON pipeline configuration
+
+common options:
+    --prefix <prefix>        prefix all the ids of the output
+    --separator <separator>  separator used after the prefix (default \":\")";
+
+struct Args {
+    positionals: Vec<String>,
+    options: HashMap<String, Vec<String>>,
+}
+
+impl Args {
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Self, String> {
+        let mut positionals = vec![];
+        let mut options = HashMap::new();
+        while let Some(arg) = args.next() {
+            if let Some(name) = arg.strip_prefix("--") {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("missing value for {}", arg))?;
+                options
+                    .entry(name.to_string())
+                    .or_insert_with(Vec::new)
+                    .push(value);
+            } else {
+                positionals.push(arg);
+            }
+        }
+        Ok(Args {
+            positionals,
+            options,
+        })
+    }
+
+    fn positionals(&self, nb: usize) -> Result<&[String], String> {
+        if self.positionals.len() != nb {
+            return Err(format!(
+                "expected {} arguments, got {}",
+                nb,
+                self.positionals.len()
+            ));
+        }
+        Ok(&self.positionals)
+    }
+
+    fn all(&self, name: &str) -> &[String] {
+        self.options.get(name).map_or(&[], |v| &v[..])
+    }
+
+    fn opt(&self, name: &str) -> Option<&str> {
+        self.all(name).last().map(|s| s.as_str())
+    }
+}
+
+fn read(path: &str) -> Result<Collections, String> {
+    ntfs::read_collections_with_progress(path, &NoProgress).map_err(|e| e.to_string())
+}
+
+// Relations cannot be built on dangling references, so they are all reported
+// before anything else is done.
+fn read_checked(path: &str) -> Result<Collections, String> {
+    let (collections, dangling) =
+        ntfs::read_collections_checked(path).map_err(|e| e.to_string())?;
+    if !dangling.is_empty() {
+        for reference in &dangling {
+            eprintln!("{}", reference);
+        }
+        return Err(format!("{} dangling references found", dangling.len()));
+    }
+    Ok(collections)
+}
+
+fn write_output(mut collections: Collections, args: &Args, output: &str) -> Result<(), String> {
+    if let Some(p) = args.opt("prefix") {
+        let config = PrefixConfiguration::new(p).separator(args.opt("separator").unwrap_or(":"));
+        prefix::add_prefix(&mut collections, &config);
+    }
+    fs::create_dir_all(output).map_err(|e| format!("{}: {}", output, e))?;
+    ntfs::write_with_progress(output, &PtObjects::new(collections), &NoProgress)
+        .map_err(|e| e.to_string())
+}
+
+fn ntfs2ntfs(args: &Args) -> Result<(), String> {
+    let paths = args.positionals(2)?;
+    let mut collections = read(&paths[0])?;
+    let report = collections
+        .sanitize(&SanitizeConfig::default())
+        .map_err(|e| e.to_string())?;
+    eprintln!(
+        "{} objects removed, {} repaired",
+        report.removed.len(),
+        report.repaired.len()
+    );
+    write_output(collections, args, &paths[1])
+}
+
+fn parse_policy(policy: &str) -> Result<MergePolicy, String> {
+    match (policy, policy.strip_prefix("prefix:")) {
+        ("fail", _) => Ok(MergePolicy::Fail),
+        ("keep-first", _) => Ok(MergePolicy::KeepFirst),
+        (_, Some(prefix)) => Ok(MergePolicy::Prefix(prefix.to_string())),
+        _ => Err(format!("unknown conflict policy {}", policy)),
+    }
+}
+
+fn merge(args: &Args) -> Result<(), String> {
+    let paths = args.positionals(3)?;
+    let policy = parse_policy(args.opt("on-conflict").unwrap_or("fail"))?;
+    let merged = merge::merge(
+        read(&paths[0])?,
+        read(&paths[1])?,
+        &policy,
+    )
+    .map_err(|e| e.to_string())?;
+    for conflict in &merged.conflicts {
+        match conflict.new_id {
+            Some(ref new_id) => eprintln!(
+                "{} {} renamed to {}",
+                conflict.object_type, conflict.id, new_id
+            ),
+            None => eprintln!("{} {} kept from first", conflict.object_type, conflict.id),
+        }
+    }
+    write_output(merged.collections, args, &paths[2])
+}
+
+fn apply_rules(args: &Args) -> Result<(), String> {
+    let paths = args.positionals(2)?;
+    let mut collections = read(&paths[0])?;
+    let report = apply_rules::apply_rules(
+        &mut collections,
+        args.all("codes"),
+        args.all("properties"),
+        args.opt("networks-consolidation").map(String::from),
+    )
+    .map_err(|e| e.to_string())?;
+    println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    write_output(collections, args, &paths[1])
+}
+
+fn validate(args: &Args) -> Result<(), String> {
+    let paths = args.positionals(1)?;
+    let mut collections = read_checked(&paths[0])?;
+    let report = collections
+        .sanitize(&SanitizeConfig::default())
+        .map_err(|e| e.to_string())?;
+    println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    Ok(())
+}
+
+fn stats(args: &Args) -> Result<(), String> {
+    let paths = args.positionals(1)?;
+    let report = stats::compute(&PtObjects::new(read_checked(&paths[0])?));
+    if let Some(path) = args.opt("json") {
+        stats::write_json(path, &report).map_err(|e| e.to_string())?;
+    }
+    if let Some(dir) = args.opt("csv") {
+        stats::write_csv(dir, &report).map_err(|e| e.to_string())?;
+    }
+    if args.opt("json").is_none() && args.opt("csv").is_none() {
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    }
+    Ok(())
+}
+
+fn quality(args: &Args) -> Result<(), String> {
+    let paths = args.positionals(1)?;
+    let report = quality::compute(&read(&paths[0])?);
+    if let Some(path) = args.opt("json") {
+        quality::write_json(path, &report).map_err(|e| e.to_string())?;
+    }
+    if let Some(path) = args.opt("csv") {
+        quality::write_csv(path, &report).map_err(|e| e.to_string())?;
+    }
+    if args.opt("json").is_none() && args.opt("csv").is_none() {
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    }
+    if report.has_errors() {
+        return Err(format!(
+            "{} errors found",
+            report.count(quality::Severity::Error)
+        ));
+    }
+    Ok(())
+}
+
+fn run_pipeline(args: &Args) -> Result<(), String> {
+    let paths = args.positionals(1)?;
+    let config = pipeline::Config::from_path(&paths[0]).map_err(|e| e.to_string())?;
+    let report = pipeline::run(&config).map_err(|e| e.to_string())?;
+    println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    Ok(())
+}
+
+fn run() -> Result<(), String> {
+    let mut args = env::args().skip(1);
+    let command = args.next().ok_or_else(|| USAGE.to_string())?;
+    let args = Args::parse(args)?;
+    match command.as_str() {
+        "ntfs2ntfs" => ntfs2ntfs(&args),
+        "merge" => merge(&args),
+        "apply-rules" => apply_rules(&args),
+        "validate" => validate(&args),
+        "stats" => stats(&args),
+        "quality" => quality(&args),
+        "pipeline" => run_pipeline(&args),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => Err(format!("unknown command {}\n\n{}", command, USAGE)),
+    }
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,177 @@
+use collection::{Collection, Id};
+use objects::*;
+use {Collections, PtObjects};
+
+const DEFAULT_NETWORK: &str = "default_network";
+const DEFAULT_TIMEZONE: &str = "Europe/Paris";
+const DEFAULT_MODE: &str = "Bus";
+
+// This builder is only meant for tests: an id added twice is a mistake in the
+// test itself, so it panics instead of silently dropping the object.
+fn push<T: Id<T>>(collection: &mut Collection<T>, object_type: &str, object: T) {
+    if let Err(object) = collection.push(object) {
+        panic!("ModelBuilder: {} {} already exists", object_type, object.id());
+    }
+}
+
+#[derive(Default)]
+pub struct ModelBuilder {
+    collections: Collections,
+    network_id: Option<String>,
+    line_id: Option<String>,
+    route_id: Option<String>,
+}
+
+impl ModelBuilder {
+    pub fn new() -> Self {
+        ModelBuilder::default()
+    }
+
+    pub fn network(mut self, id: &str) -> Self {
+        push(
+            &mut self.collections.networks,
+            "network",
+            Network {
+                id: id.to_string(),
+                name: id.to_string(),
+                timezone: DEFAULT_TIMEZONE.to_string(),
+            },
+        );
+        self.network_id = Some(id.to_string());
+        self.line_id = None;
+        self.route_id = None;
+        self
+    }
+
+    pub fn commercial_mode(mut self, id: &str) -> Self {
+        push(
+            &mut self.collections.commercial_modes,
+            "commercial_mode",
+            CommercialMode {
+                id: id.to_string(),
+                name: id.to_string(),
+            },
+        );
+        self
+    }
+
+    pub fn physical_mode(mut self, id: &str) -> Self {
+        push(
+            &mut self.collections.physical_modes,
+            "physical_mode",
+            PhysicalMode {
+                id: id.to_string(),
+                name: id.to_string(),
+            },
+        );
+        self
+    }
+
+    pub fn line(self, id: &str) -> Self {
+        self.line_with(id, |_| {})
+    }
+
+    pub fn line_with<F: FnOnce(&mut Line)>(mut self, id: &str, f: F) -> Self {
+        if self.network_id.is_none() {
+            self = self.network(DEFAULT_NETWORK);
+        }
+        if self.collections.commercial_modes.get(DEFAULT_MODE).is_none() {
+            self = self.commercial_mode(DEFAULT_MODE);
+        }
+        let mut line = Line {
+            id: id.to_string(),
+            name: id.to_string(),
+            network_id: self.network_id.clone().unwrap(),
+            commercial_mode_id: DEFAULT_MODE.to_string(),
+            booking_rule_id: None,
+            color: None,
+            text_color: None,
+            on_demand_zone_id: None,
+        };
+        f(&mut line);
+        push(&mut self.collections.lines, "line", line);
+        self.line_id = Some(id.to_string());
+        self.route_id = None;
+        self
+    }
+
+    pub fn route(mut self, id: &str) -> Self {
+        if self.line_id.is_none() {
+            let line_id = format!("{}_line", id);
+            self = self.line(&line_id);
+        }
+        push(
+            &mut self.collections.routes,
+            "route",
+            Route {
+                id: id.to_string(),
+                name: id.to_string(),
+                line_id: self.line_id.clone().unwrap(),
+            },
+        );
+        self.route_id = Some(id.to_string());
+        self
+    }
+
+    pub fn vj(self, id: &str) -> Self {
+        self.vj_with(id, |_| {})
+    }
+
+    pub fn vj_with<F: FnOnce(&mut VehicleJourney)>(mut self, id: &str, f: F) -> Self {
+        if self.route_id.is_none() {
+            let route_id = match self.line_id {
+                Some(ref line_id) => format!("{}_route", line_id),
+                None => format!("{}_route", id),
+            };
+            self = self.route(&route_id);
+        }
+        if self.collections.physical_modes.get(DEFAULT_MODE).is_none() {
+            self = self.physical_mode(DEFAULT_MODE);
+        }
+        let mut vj = VehicleJourney {
+            id: id.to_string(),
+            route_id: self.route_id.clone().unwrap(),
+            physical_mode_id: DEFAULT_MODE.to_string(),
+            booking_rule_id: None,
+            wheelchair_accessible: None,
+            bikes_allowed: None,
+            stroller_allowed: None,
+            luggage_allowed: None,
+            vehicle_type_id: None,
+            on_demand_zone_id: None,
+        };
+        f(&mut vj);
+        push(&mut self.collections.vehicle_journeys, "vehicle_journey", vj);
+        self
+    }
+
+    pub fn build(self) -> PtObjects {
+        PtObjects::new(self.collections)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_modes_are_shared_between_lines() {
+        let collections = ModelBuilder::new()
+            .line("l1")
+            .vj("v1")
+            .line("l2")
+            .vj("v2")
+            .build()
+            .into_collections();
+        assert_eq!(collections.networks.len(), 1);
+        assert_eq!(collections.commercial_modes.len(), 1);
+        assert_eq!(collections.physical_modes.len(), 1);
+        assert_eq!(collections.vehicle_journeys.len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "ModelBuilder: line l1 already exists")]
+    fn duplicate_line_panics() {
+        let _ = ModelBuilder::new().line("l1").vj("v1").line("l1");
+    }
+}
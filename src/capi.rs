@@ -0,0 +1,90 @@
+// The model is handed to C as an opaque pointer. Functions returning a
+// pointer return null on failure, the others return 0 on success and -1
+// on failure.
+//
+// Every pointer given to these functions must be null or valid: models
+// must come from navitia_model_read_ntfs and not be freed yet, strings must
+// be nul-terminated.
+#![allow(clippy::missing_safety_doc)]
+
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+
+use error::Result;
+use prefix::{self, PrefixConfiguration};
+use progress::NoProgress;
+use sanitize::SanitizeConfig;
+use {ntfs, PtObjects};
+
+unsafe fn to_str<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        return None;
+    }
+    CStr::from_ptr(s).to_str().ok()
+}
+
+fn status<T>(result: Result<T>) -> c_int {
+    match result {
+        Ok(_) => 0,
+        Err(_) => -1,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn navitia_model_read_ntfs(path: *const c_char) -> *mut PtObjects {
+    let path = match to_str(path) {
+        Some(path) => path,
+        None => return ptr::null_mut(),
+    };
+    match ntfs::read_with_progress(path, &NoProgress) {
+        Ok(pt_objects) => Box::into_raw(Box::new(pt_objects)),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn navitia_model_write_ntfs(
+    model: *const PtObjects,
+    path: *const c_char,
+) -> c_int {
+    match (model.as_ref(), to_str(path)) {
+        (Some(model), Some(path)) => status(ntfs::write_with_progress(path, model, &NoProgress)),
+        _ => -1,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn navitia_model_sanitize(model: *mut PtObjects) -> c_int {
+    match model.as_mut() {
+        Some(model) => {
+            let mut result = Ok(());
+            model.update(|c| result = c.sanitize(&SanitizeConfig::default()).map(|_| ()));
+            status(result)
+        }
+        None => -1,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn navitia_model_add_prefix(
+    model: *mut PtObjects,
+    prefix: *const c_char,
+    separator: *const c_char,
+) -> c_int {
+    match (model.as_mut(), to_str(prefix), to_str(separator)) {
+        (Some(model), Some(prefix), Some(separator)) => {
+            let config = PrefixConfiguration::new(prefix).separator(separator);
+            model.update(|c| prefix::add_prefix(c, &config));
+            0
+        }
+        _ => -1,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn navitia_model_free(model: *mut PtObjects) {
+    if !model.is_null() {
+        drop(Box::from_raw(model));
+    }
+}
@@ -1,15 +1,17 @@
 use std::collections::HashMap;
 use std::marker::PhantomData;
+use std::mem;
 use std::iter;
 use std::slice;
 use std::ops;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::Error;
 
 pub trait Id<T> {
     fn id(&self) -> &str;
 }
 
-// TODO: no serde on Idx
-#[derive(Derivative, Serialize, Deserialize, Debug)]
+#[derive(Derivative, Debug)]
 #[derivative(Copy(bound = ""), Clone(bound = ""), PartialEq(bound = ""), Eq(bound = ""),
              Hash(bound = ""))]
 pub struct Idx<T>(u32, PhantomData<T>);
@@ -23,8 +25,7 @@ impl<T> Idx<T> {
     }
 }
 
-// TODO: serde only on objects
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Debug)]
 pub struct Collection<T> {
     objects: Vec<T>,
     id_to_idx: HashMap<String, Idx<T>>,
@@ -46,6 +47,22 @@ impl<T: Id<T>> Collection<T> {
             .collect();
         res
     }
+
+    pub fn push(&mut self, item: T) -> Result<Idx<T>, T> {
+        if self.id_to_idx.contains_key(item.id()) {
+            return Err(item);
+        }
+        let idx = Idx::new(self.objects.len());
+        self.id_to_idx.insert(item.id().to_string(), idx);
+        self.objects.push(item);
+        Ok(idx)
+    }
+
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, f: F) {
+        let mut objects = mem::take(self).into_vec();
+        objects.retain(f);
+        *self = Collection::from_vec(objects);
+    }
 }
 
 impl<T> Default for Collection<T> {
@@ -65,13 +82,53 @@ impl<T> Collection<T> {
             .map(|(idx, obj)| (Idx::new(idx), obj))
     }
 
+    pub fn iter_mut(&mut self) -> slice::IterMut<'_, T> {
+        self.objects.iter_mut()
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    pub fn into_vec(self) -> Vec<T> {
+        self.objects
+    }
+
     pub fn get_idx(&self, id: &str) -> Option<Idx<T>> {
-        self.id_to_idx.get(id).map(Clone::clone)
+        self.id_to_idx.get(id).cloned()
     }
 
     pub fn get(&self, id: &str) -> Option<&T> {
         self.get_idx(id).map(|idx| &self[idx])
     }
+
+    pub fn get_mut(&mut self, id: &str) -> Option<&mut T> {
+        let idx = self.get_idx(id)?;
+        Some(&mut self.objects[idx.get()])
+    }
+}
+
+impl<T: Serialize> Serialize for Collection<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.objects.serialize(serializer)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for Collection<T>
+where
+    T: Id<T> + Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let collection = Collection::from_vec(Vec::deserialize(deserializer)?);
+        if collection.id_to_idx.len() != collection.objects.len() {
+            return Err(D::Error::custom("duplicate identifiers in collection"));
+        }
+        Ok(collection)
+    }
 }
 
 impl<T> ops::Index<Idx<T>> for Collection<T> {
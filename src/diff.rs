@@ -0,0 +1,170 @@
+use std::collections::HashSet;
+use serde::Serialize;
+use serde_json::{self, Value};
+
+use collection::{Collection, Id};
+use objects::{ObjectCode, TicketPrice, TicketUsePerimeter};
+use Collections;
+
+#[derive(Serialize, Debug)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Modification {
+    pub id: String,
+    pub changes: Vec<FieldChange>,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct CollectionDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub modified: Vec<Modification>,
+}
+
+impl CollectionDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+// For objects without an id, which can only be added or removed.
+#[derive(Serialize, Debug)]
+pub struct SetDiff<T> {
+    pub added: Vec<T>,
+    pub removed: Vec<T>,
+}
+
+impl<T> SetDiff<T> {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct ChangeSet {
+    pub networks: CollectionDiff,
+    pub commercial_modes: CollectionDiff,
+    pub physical_modes: CollectionDiff,
+    pub lines: CollectionDiff,
+    pub routes: CollectionDiff,
+    pub vehicle_journeys: CollectionDiff,
+    pub occupancies: CollectionDiff,
+    pub booking_rules: CollectionDiff,
+    pub vehicle_types: CollectionDiff,
+    pub on_demand_zones: CollectionDiff,
+    pub tickets: CollectionDiff,
+    pub ticket_uses: CollectionDiff,
+    pub object_codes: SetDiff<ObjectCode>,
+    pub ticket_prices: SetDiff<TicketPrice>,
+    pub ticket_use_perimeters: SetDiff<TicketUsePerimeter>,
+}
+
+impl ChangeSet {
+    pub fn is_empty(&self) -> bool {
+        self.networks.is_empty()
+            && self.commercial_modes.is_empty()
+            && self.physical_modes.is_empty()
+            && self.lines.is_empty()
+            && self.routes.is_empty()
+            && self.vehicle_journeys.is_empty()
+            && self.occupancies.is_empty()
+            && self.booking_rules.is_empty()
+            && self.vehicle_types.is_empty()
+            && self.on_demand_zones.is_empty()
+            && self.tickets.is_empty()
+            && self.ticket_uses.is_empty()
+            && self.object_codes.is_empty()
+            && self.ticket_prices.is_empty()
+            && self.ticket_use_perimeters.is_empty()
+    }
+}
+
+fn field_changes<T: Serialize>(old: &T, new: &T) -> Vec<FieldChange> {
+    let old = serde_json::to_value(old).unwrap();
+    let new = serde_json::to_value(new).unwrap();
+    match (old, new) {
+        (Value::Object(old), Value::Object(mut new)) => {
+            let mut changes = vec![];
+            for (field, old_value) in old {
+                let new_value = new.remove(&field).unwrap_or(Value::Null);
+                if old_value != new_value {
+                    changes.push(FieldChange {
+                        field,
+                        old: old_value,
+                        new: new_value,
+                    });
+                }
+            }
+            changes
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn compare_collection<T>(old: &Collection<T>, new: &Collection<T>) -> CollectionDiff
+where
+    T: Id<T> + Serialize + PartialEq,
+{
+    let mut diff = CollectionDiff::default();
+    for (_, old_obj) in old.iter() {
+        match new.get(old_obj.id()) {
+            None => diff.removed.push(old_obj.id().to_string()),
+            Some(new_obj) if new_obj != old_obj => diff.modified.push(Modification {
+                id: old_obj.id().to_string(),
+                changes: field_changes(old_obj, new_obj),
+            }),
+            Some(_) => {}
+        }
+    }
+    for (_, new_obj) in new.iter() {
+        if old.get(new_obj.id()).is_none() {
+            diff.added.push(new_obj.id().to_string());
+        }
+    }
+    diff
+}
+
+// Objects are compared through their serialization, as prices hold a float
+// and cannot be hashed.
+fn compare_set<T: Serialize + Clone>(old: &[T], new: &[T]) -> SetDiff<T> {
+    let key = |obj: &T| serde_json::to_string(obj).unwrap();
+    let old_keys: HashSet<_> = old.iter().map(key).collect();
+    let new_keys: HashSet<_> = new.iter().map(key).collect();
+    SetDiff {
+        added: new
+            .iter()
+            .filter(|obj| !old_keys.contains(&key(obj)))
+            .cloned()
+            .collect(),
+        removed: old
+            .iter()
+            .filter(|obj| !new_keys.contains(&key(obj)))
+            .cloned()
+            .collect(),
+    }
+}
+
+pub fn compare(old: &Collections, new: &Collections) -> ChangeSet {
+    ChangeSet {
+        networks: compare_collection(&old.networks, &new.networks),
+        commercial_modes: compare_collection(&old.commercial_modes, &new.commercial_modes),
+        physical_modes: compare_collection(&old.physical_modes, &new.physical_modes),
+        lines: compare_collection(&old.lines, &new.lines),
+        routes: compare_collection(&old.routes, &new.routes),
+        vehicle_journeys: compare_collection(&old.vehicle_journeys, &new.vehicle_journeys),
+        occupancies: compare_collection(&old.occupancies, &new.occupancies),
+        booking_rules: compare_collection(&old.booking_rules, &new.booking_rules),
+        vehicle_types: compare_collection(&old.vehicle_types, &new.vehicle_types),
+        on_demand_zones: compare_collection(&old.on_demand_zones, &new.on_demand_zones),
+        tickets: compare_collection(&old.tickets, &new.tickets),
+        ticket_uses: compare_collection(&old.ticket_uses, &new.ticket_uses),
+        object_codes: compare_set(&old.object_codes, &new.object_codes),
+        ticket_prices: compare_set(&old.ticket_prices, &new.ticket_prices),
+        ticket_use_perimeters: compare_set(&old.ticket_use_perimeters, &new.ticket_use_perimeters),
+    }
+}
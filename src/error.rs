@@ -0,0 +1,74 @@
+use std::io;
+use std::path::Path;
+use csv;
+use serde_json;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("{object_type} {id} not found")]
+    ObjectNotFound {
+        object_type: &'static str,
+        id: String,
+    },
+    #[error("{object_type} {id} already exists")]
+    DuplicateId {
+        object_type: &'static str,
+        id: String,
+    },
+    // `file` and `line` locate the object when it was read from an NTFS.
+    #[error("{}{object_type} {id} has an invalid reference: {reference}", location(.file, .line))]
+    InvalidReference {
+        object_type: &'static str,
+        id: String,
+        reference: String,
+        file: Option<&'static str>,
+        line: Option<u64>,
+    },
+    #[error("invalid configuration: {0}")]
+    InvalidConfig(String),
+    #[error("operation cancelled")]
+    Cancelled,
+    #[error("{path}: {source}")]
+    Io { path: String, source: io::Error },
+    #[error("{path}: {source}")]
+    Csv { path: String, source: csv::Error },
+    #[error("{path}: {source}")]
+    Json {
+        path: String,
+        source: serde_json::Error,
+    },
+    #[error("a writer thread panicked")]
+    WriterPanicked,
+}
+
+fn location(file: &Option<&'static str>, line: &Option<u64>) -> String {
+    match (*file, *line) {
+        (Some(file), Some(line)) => format!("{}:{}: ", file, line),
+        (Some(file), None) => format!("{}: ", file),
+        _ => String::new(),
+    }
+}
+
+pub(crate) fn io_error(path: &Path, source: io::Error) -> Error {
+    Error::Io {
+        path: path.display().to_string(),
+        source,
+    }
+}
+
+pub(crate) fn csv_error(path: &Path, source: csv::Error) -> Error {
+    Error::Csv {
+        path: path.display().to_string(),
+        source,
+    }
+}
+
+pub(crate) fn json_error(path: &Path, source: serde_json::Error) -> Error {
+    Error::Json {
+        path: path.display().to_string(),
+        source,
+    }
+}
+
+pub type Result<T> = ::std::result::Result<T, Error>;
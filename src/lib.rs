@@ -1,4 +1,14 @@
+extern crate bincode;
 extern crate csv;
+// pyo3 macros expand to ::core paths
+#[cfg(feature = "python")]
+extern crate core;
+#[cfg(feature = "python")]
+extern crate pyo3;
+#[cfg(feature = "parallel")]
+extern crate rayon;
+#[cfg(feature = "sqlite")]
+extern crate rusqlite;
 #[macro_use]
 extern crate derivative;
 #[macro_use]
@@ -6,18 +16,45 @@ extern crate get_corresponding_derive;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+extern crate serde_json;
+extern crate thiserror;
 
+pub mod accessibility;
+pub mod apply_rules;
+pub mod builder;
+#[cfg(feature = "capi")]
+pub mod capi;
 pub mod collection;
 pub mod objects;
 pub mod relations;
+pub mod diff;
+pub mod error;
+pub mod merge;
 pub mod ntfs;
+pub mod pipeline;
+pub mod prefix;
+pub mod progress;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod quality;
+pub mod query;
+pub mod sanitize;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod stats;
 
+use std::io;
 use std::ops;
 
-use collection::Collection;
+use collection::{Collection, Id, Idx};
+use error::{Error, Result};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use objects::*;
 use relations::{GetCorresponding, IdxSet, OneToMany};
 
+// to be incremented each time the serialized layout of Collections changes
+const SNAPSHOT_VERSION: u32 = 9;
+
 #[derive(Derivative, Serialize, Deserialize, Debug)]
 #[derivative(Default)]
 pub struct Collections {
@@ -27,6 +64,59 @@ pub struct Collections {
     pub routes: Collection<Route>,
     pub vehicle_journeys: Collection<VehicleJourney>,
     pub physical_modes: Collection<PhysicalMode>,
+    pub occupancies: Collection<Occupancy>,
+    pub booking_rules: Collection<BookingRule>,
+    pub vehicle_types: Collection<VehicleType>,
+    pub on_demand_zones: Collection<OnDemandZone>,
+    pub object_codes: Vec<ObjectCode>,
+    pub tickets: Collection<Ticket>,
+    pub ticket_prices: Vec<TicketPrice>,
+    pub ticket_uses: Collection<TicketUse>,
+    pub ticket_use_perimeters: Vec<TicketUsePerimeter>,
+}
+
+impl Collections {
+    pub(crate) fn object_exists(&self, object_type: ObjectType, id: &str) -> bool {
+        match object_type {
+            ObjectType::Network => self.networks.get(id).is_some(),
+            ObjectType::Line => self.lines.get(id).is_some(),
+            ObjectType::Route => self.routes.get(id).is_some(),
+            ObjectType::VehicleJourney => self.vehicle_journeys.get(id).is_some(),
+        }
+    }
+
+    pub(crate) fn missing_object(&self, object_type: ObjectType, id: &str) -> Option<String> {
+        if self.object_exists(object_type, id) {
+            None
+        } else {
+            Some(format!("object_id {} not found", id))
+        }
+    }
+}
+
+fn not_found(object_type: &'static str, id: &str) -> Error {
+    Error::ObjectNotFound {
+        object_type,
+        id: id.to_string(),
+    }
+}
+
+fn duplicate(object_type: &'static str, id: &str) -> Error {
+    Error::DuplicateId {
+        object_type,
+        id: id.to_string(),
+    }
+}
+
+fn check_opt_reference<T: Id<T>>(
+    collection: &Collection<T>,
+    object_type: &'static str,
+    id: &Option<String>,
+) -> Result<()> {
+    match *id {
+        Some(ref id) if collection.get(id).is_none() => Err(not_found(object_type, id)),
+        _ => Ok(()),
+    }
 }
 
 #[derive(GetCorresponding)]
@@ -37,25 +127,168 @@ pub struct PtObjects {
     lines_to_routes: OneToMany<Line, Route>,
     routes_to_vehicle_journeys: OneToMany<Route, VehicleJourney>,
     physical_modes_to_vehicle_journeys: OneToMany<PhysicalMode, VehicleJourney>,
+    vehicle_journeys_to_occupancies: OneToMany<VehicleJourney, Occupancy>,
+    tickets_to_ticket_uses: OneToMany<Ticket, TicketUse>,
 }
 impl PtObjects {
     pub fn new(c: Collections) -> Self {
         PtObjects {
-            network_to_lines: OneToMany::new(&c.networks, &c.lines),
-            commercial_modes_to_lines: OneToMany::new(&c.commercial_modes, &c.lines),
-            lines_to_routes: OneToMany::new(&c.lines, &c.routes),
-            routes_to_vehicle_journeys: OneToMany::new(&c.routes, &c.vehicle_journeys),
-            physical_modes_to_vehicle_journeys: OneToMany::new(
-                &c.physical_modes,
-                &c.vehicle_journeys,
-            ),
+            network_to_lines: OneToMany::new(|c| (&c.networks, &c.lines)),
+            commercial_modes_to_lines: OneToMany::new(|c| (&c.commercial_modes, &c.lines)),
+            lines_to_routes: OneToMany::new(|c| (&c.lines, &c.routes)),
+            routes_to_vehicle_journeys: OneToMany::new(|c| (&c.routes, &c.vehicle_journeys)),
+            physical_modes_to_vehicle_journeys: OneToMany::new(|c| {
+                (&c.physical_modes, &c.vehicle_journeys)
+            }),
+            vehicle_journeys_to_occupancies: OneToMany::new(|c| {
+                (&c.vehicle_journeys, &c.occupancies)
+            }),
+            tickets_to_ticket_uses: OneToMany::new(|c| (&c.tickets, &c.ticket_uses)),
             collections: c,
         }
     }
+
+    pub fn build_relations(&self) {
+        let c = &self.collections;
+        self.network_to_lines.build(c);
+        self.commercial_modes_to_lines.build(c);
+        self.lines_to_routes.build(c);
+        self.routes_to_vehicle_journeys.build(c);
+        self.physical_modes_to_vehicle_journeys.build(c);
+        self.vehicle_journeys_to_occupancies.build(c);
+        self.tickets_to_ticket_uses.build(c);
+    }
+
+    pub fn update<F: FnOnce(&mut Collections)>(&mut self, f: F) {
+        f(&mut self.collections);
+        self.network_to_lines.invalidate();
+        self.commercial_modes_to_lines.invalidate();
+        self.lines_to_routes.invalidate();
+        self.routes_to_vehicle_journeys.invalidate();
+        self.physical_modes_to_vehicle_journeys.invalidate();
+        self.vehicle_journeys_to_occupancies.invalidate();
+        self.tickets_to_ticket_uses.invalidate();
+    }
+
+    pub fn add_line(&mut self, line: Line) -> Result<Idx<Line>> {
+        if self.networks.get(&line.network_id).is_none() {
+            return Err(not_found("network", &line.network_id));
+        }
+        if self.commercial_modes.get(&line.commercial_mode_id).is_none() {
+            return Err(not_found("commercial_mode", &line.commercial_mode_id));
+        }
+        check_opt_reference(&self.booking_rules, "booking_rule", &line.booking_rule_id)?;
+        check_opt_reference(&self.on_demand_zones, "on_demand_zone", &line.on_demand_zone_id)?;
+        let idx = self.collections
+            .lines
+            .push(line)
+            .map_err(|l| duplicate("line", &l.id))?;
+        self.network_to_lines.invalidate();
+        self.commercial_modes_to_lines.invalidate();
+        Ok(idx)
+    }
+
+    pub fn add_route(&mut self, route: Route) -> Result<Idx<Route>> {
+        if self.lines.get(&route.line_id).is_none() {
+            return Err(not_found("line", &route.line_id));
+        }
+        let idx = self.collections
+            .routes
+            .push(route)
+            .map_err(|r| duplicate("route", &r.id))?;
+        self.lines_to_routes.invalidate();
+        Ok(idx)
+    }
+
+    pub fn add_vehicle_journey(&mut self, vj: VehicleJourney) -> Result<Idx<VehicleJourney>> {
+        if self.routes.get(&vj.route_id).is_none() {
+            return Err(not_found("route", &vj.route_id));
+        }
+        if self.physical_modes.get(&vj.physical_mode_id).is_none() {
+            return Err(not_found("physical_mode", &vj.physical_mode_id));
+        }
+        check_opt_reference(&self.booking_rules, "booking_rule", &vj.booking_rule_id)?;
+        check_opt_reference(&self.vehicle_types, "vehicle_type", &vj.vehicle_type_id)?;
+        check_opt_reference(&self.on_demand_zones, "on_demand_zone", &vj.on_demand_zone_id)?;
+        let idx = self.collections
+            .vehicle_journeys
+            .push(vj)
+            .map_err(|vj| duplicate("vehicle_journey", &vj.id))?;
+        self.routes_to_vehicle_journeys.invalidate();
+        self.physical_modes_to_vehicle_journeys.invalidate();
+        Ok(idx)
+    }
+
+    pub fn into_collections(self) -> Collections {
+        self.collections
+    }
+
+    pub fn to_binary(&self) -> bincode::Result<Vec<u8>> {
+        bincode::serialize(&(SNAPSHOT_VERSION, &self.collections))
+    }
+
+    pub fn from_binary(bytes: &[u8]) -> bincode::Result<Self> {
+        let mut reader = io::Cursor::new(bytes);
+        let version: u32 = bincode::deserialize_from(&mut reader)?;
+        if version != SNAPSHOT_VERSION {
+            return Err(Box::new(bincode::ErrorKind::Custom(format!(
+                "snapshot version {} is not supported (expected {})",
+                version, SNAPSHOT_VERSION
+            ))));
+        }
+        let collections = bincode::deserialize_from(&mut reader)?;
+        Ok(PtObjects::new(collections))
+    }
+}
+impl Serialize for PtObjects {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        self.collections.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for PtObjects {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Collections::deserialize(deserializer).map(PtObjects::new)
+    }
 }
+
 impl ops::Deref for PtObjects {
     type Target = Collections;
     fn deref(&self) -> &Self::Target {
         &self.collections
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builder::ModelBuilder;
+
+    #[test]
+    fn add_vehicle_journey_checks_optional_references() {
+        let mut pt_objects = ModelBuilder::new().route("r1").vj("v1").build();
+        let vj = |vehicle_type_id: Option<&str>| VehicleJourney {
+            id: "v2".to_string(),
+            route_id: "r1".to_string(),
+            physical_mode_id: "Bus".to_string(),
+            booking_rule_id: None,
+            wheelchair_accessible: None,
+            bikes_allowed: None,
+            stroller_allowed: None,
+            luggage_allowed: None,
+            vehicle_type_id: vehicle_type_id.map(String::from),
+            on_demand_zone_id: None,
+        };
+        match pt_objects.add_vehicle_journey(vj(Some("unknown"))) {
+            Err(Error::ObjectNotFound { object_type, id }) => {
+                assert_eq!(object_type, "vehicle_type");
+                assert_eq!(id, "unknown");
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert!(pt_objects.add_vehicle_journey(vj(None)).is_ok());
+    }
+}
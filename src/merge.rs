@@ -0,0 +1,383 @@
+use std::collections::{HashMap, HashSet};
+
+use collection::{Collection, Id};
+use error::{Error, Result};
+use objects::{ObjectCode, ObjectType, TicketUsePerimeter};
+use Collections;
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MergePolicy {
+    #[default]
+    Fail,
+    KeepFirst,
+    Prefix(String),
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub object_type: &'static str,
+    pub id: String,
+    pub new_id: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct Merged {
+    pub collections: Collections,
+    pub conflicts: Vec<Conflict>,
+}
+
+type Renamed = HashMap<String, String>;
+
+fn merge_collection<T, F>(
+    object_type: &'static str,
+    first: Collection<T>,
+    others: Vec<T>,
+    policy: &MergePolicy,
+    set_id: F,
+    conflicts: &mut Vec<Conflict>,
+) -> Result<(Collection<T>, Renamed)>
+where
+    T: Id<T> + PartialEq,
+    F: Fn(&mut T, String),
+{
+    let mut renamed = Renamed::default();
+    let mut objects = first.into_vec();
+    let mut ids: HashMap<String, usize> = objects
+        .iter()
+        .enumerate()
+        .map(|(i, o)| (o.id().to_string(), i))
+        .collect();
+    for mut obj in others {
+        let id = obj.id().to_string();
+        match ids.get(&id) {
+            None => {}
+            Some(&i) if objects[i] == obj => continue,
+            Some(_) => match *policy {
+                MergePolicy::Fail => return Err(Error::DuplicateId { object_type, id }),
+                MergePolicy::KeepFirst => {
+                    conflicts.push(Conflict {
+                        object_type,
+                        id,
+                        new_id: None,
+                    });
+                    continue;
+                }
+                MergePolicy::Prefix(ref prefix) => {
+                    let new_id = format!("{}{}", prefix, id);
+                    if ids.contains_key(&new_id) {
+                        return Err(Error::DuplicateId {
+                            object_type,
+                            id: new_id,
+                        });
+                    }
+                    set_id(&mut obj, new_id.clone());
+                    renamed.insert(id.clone(), new_id.clone());
+                    conflicts.push(Conflict {
+                        object_type,
+                        id,
+                        new_id: Some(new_id),
+                    });
+                }
+            },
+        }
+        ids.insert(obj.id().to_string(), objects.len());
+        objects.push(obj);
+    }
+    Ok((Collection::from_vec(objects), renamed))
+}
+
+fn update_ref(id: &mut String, renamed: &Renamed) {
+    if let Some(new_id) = renamed.get(id) {
+        *id = new_id.clone();
+    }
+}
+
+fn update_opt_ref(id: &mut Option<String>, renamed: &Renamed) {
+    if let Some(ref mut id) = *id {
+        update_ref(id, renamed);
+    }
+}
+
+pub fn merge(
+    first: Collections,
+    other: Collections,
+    policy: &MergePolicy,
+) -> Result<Merged> {
+    let mut conflicts = vec![];
+    let (networks, networks_renamed) = merge_collection(
+        "network",
+        first.networks,
+        other.networks.into_vec(),
+        policy,
+        |o, id| o.id = id,
+        &mut conflicts,
+    )?;
+    let (commercial_modes, commercial_modes_renamed) = merge_collection(
+        "commercial_mode",
+        first.commercial_modes,
+        other.commercial_modes.into_vec(),
+        policy,
+        |o, id| o.id = id,
+        &mut conflicts,
+    )?;
+    let (physical_modes, physical_modes_renamed) = merge_collection(
+        "physical_mode",
+        first.physical_modes,
+        other.physical_modes.into_vec(),
+        policy,
+        |o, id| o.id = id,
+        &mut conflicts,
+    )?;
+    let (booking_rules, booking_rules_renamed) = merge_collection(
+        "booking_rule",
+        first.booking_rules,
+        other.booking_rules.into_vec(),
+        policy,
+        |o, id| o.id = id,
+        &mut conflicts,
+    )?;
+
+    let (vehicle_types, vehicle_types_renamed) = merge_collection(
+        "vehicle_type",
+        first.vehicle_types,
+        other.vehicle_types.into_vec(),
+        policy,
+        |o, id| o.id = id,
+        &mut conflicts,
+    )?;
+
+    let mut other_zones = other.on_demand_zones.into_vec();
+    for zone in &mut other_zones {
+        update_opt_ref(&mut zone.booking_rule_id, &booking_rules_renamed);
+    }
+    let (on_demand_zones, on_demand_zones_renamed) = merge_collection(
+        "on_demand_zone",
+        first.on_demand_zones,
+        other_zones,
+        policy,
+        |o, id| o.id = id,
+        &mut conflicts,
+    )?;
+
+    let mut other_lines = other.lines.into_vec();
+    for line in &mut other_lines {
+        update_ref(&mut line.network_id, &networks_renamed);
+        update_ref(&mut line.commercial_mode_id, &commercial_modes_renamed);
+        update_opt_ref(&mut line.booking_rule_id, &booking_rules_renamed);
+        update_opt_ref(&mut line.on_demand_zone_id, &on_demand_zones_renamed);
+    }
+    let (lines, lines_renamed) = merge_collection(
+        "line",
+        first.lines,
+        other_lines,
+        policy,
+        |o, id| o.id = id,
+        &mut conflicts,
+    )?;
+
+    let mut other_routes = other.routes.into_vec();
+    for route in &mut other_routes {
+        update_ref(&mut route.line_id, &lines_renamed);
+    }
+    let (routes, routes_renamed) = merge_collection(
+        "route",
+        first.routes,
+        other_routes,
+        policy,
+        |o, id| o.id = id,
+        &mut conflicts,
+    )?;
+
+    let mut other_vjs = other.vehicle_journeys.into_vec();
+    for vj in &mut other_vjs {
+        update_ref(&mut vj.route_id, &routes_renamed);
+        update_ref(&mut vj.physical_mode_id, &physical_modes_renamed);
+        update_opt_ref(&mut vj.booking_rule_id, &booking_rules_renamed);
+        update_opt_ref(&mut vj.vehicle_type_id, &vehicle_types_renamed);
+        update_opt_ref(&mut vj.on_demand_zone_id, &on_demand_zones_renamed);
+    }
+    let (vehicle_journeys, vehicle_journeys_renamed) = merge_collection(
+        "vehicle_journey",
+        first.vehicle_journeys,
+        other_vjs,
+        policy,
+        |o, id| o.id = id,
+        &mut conflicts,
+    )?;
+
+    let mut other_occupancies = other.occupancies.into_vec();
+    for occupancy in &mut other_occupancies {
+        update_ref(
+            &mut occupancy.vehicle_journey_id,
+            &vehicle_journeys_renamed,
+        );
+    }
+    let (occupancies, _) = merge_collection(
+        "occupancy",
+        first.occupancies,
+        other_occupancies,
+        policy,
+        |o, id| o.id = id,
+        &mut conflicts,
+    )?;
+
+    let (tickets, tickets_renamed) = merge_collection(
+        "ticket",
+        first.tickets,
+        other.tickets.into_vec(),
+        policy,
+        |o, id| o.id = id,
+        &mut conflicts,
+    )?;
+
+    let mut ticket_prices = first.ticket_prices;
+    for mut price in other.ticket_prices {
+        update_ref(&mut price.ticket_id, &tickets_renamed);
+        if !ticket_prices.contains(&price) {
+            ticket_prices.push(price);
+        }
+    }
+
+    let mut other_ticket_uses = other.ticket_uses.into_vec();
+    for ticket_use in &mut other_ticket_uses {
+        update_ref(&mut ticket_use.ticket_id, &tickets_renamed);
+    }
+    let (ticket_uses, ticket_uses_renamed) = merge_collection(
+        "ticket_use",
+        first.ticket_uses,
+        other_ticket_uses,
+        policy,
+        |o, id| o.id = id,
+        &mut conflicts,
+    )?;
+
+    let renamed_objects = |object_type| match object_type {
+        ObjectType::Network => &networks_renamed,
+        ObjectType::Line => &lines_renamed,
+        ObjectType::Route => &routes_renamed,
+        ObjectType::VehicleJourney => &vehicle_journeys_renamed,
+    };
+    let mut object_codes = first.object_codes;
+    let mut seen_codes: HashSet<ObjectCode> = object_codes.iter().cloned().collect();
+    for mut code in other.object_codes {
+        update_ref(&mut code.object_id, renamed_objects(code.object_type));
+        if seen_codes.insert(code.clone()) {
+            object_codes.push(code);
+        }
+    }
+
+    let mut ticket_use_perimeters = first.ticket_use_perimeters;
+    let mut seen_perimeters: HashSet<TicketUsePerimeter> =
+        ticket_use_perimeters.iter().cloned().collect();
+    for mut perimeter in other.ticket_use_perimeters {
+        update_ref(&mut perimeter.ticket_use_id, &ticket_uses_renamed);
+        update_ref(
+            &mut perimeter.object_id,
+            renamed_objects(perimeter.object_type),
+        );
+        if seen_perimeters.insert(perimeter.clone()) {
+            ticket_use_perimeters.push(perimeter);
+        }
+    }
+
+    Ok(Merged {
+        collections: Collections {
+            networks,
+            commercial_modes,
+            lines,
+            routes,
+            vehicle_journeys,
+            physical_modes,
+            occupancies,
+            booking_rules,
+            vehicle_types,
+            on_demand_zones,
+            object_codes,
+            tickets,
+            ticket_prices,
+            ticket_uses,
+            ticket_use_perimeters,
+        },
+        conflicts,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builder::ModelBuilder;
+
+    fn first() -> Collections {
+        ModelBuilder::new()
+            .line("l1")
+            .route("r1")
+            .vj("v1")
+            .build()
+            .into_collections()
+    }
+
+    fn other() -> Collections {
+        ModelBuilder::new()
+            .line_with("l1", |l| l.name = "other".to_string())
+            .route("r2")
+            .vj("v2")
+            .build()
+            .into_collections()
+    }
+
+    #[test]
+    fn fail_policy_rejects_conflicting_ids() {
+        match merge(first(), other(), &MergePolicy::Fail) {
+            Err(Error::DuplicateId { object_type, id }) => {
+                assert_eq!(object_type, "line");
+                assert_eq!(id, "l1");
+            }
+            other => panic!("unexpected result {:?}", other.map(|m| m.conflicts)),
+        }
+    }
+
+    #[test]
+    fn identical_objects_are_not_conflicts() {
+        let merged = merge(first(), first(), &MergePolicy::Fail).unwrap();
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(merged.collections.lines.len(), 1);
+        assert_eq!(merged.collections.vehicle_journeys.len(), 1);
+    }
+
+    #[test]
+    fn keep_first_policy_drops_the_other_object() {
+        let merged = merge(first(), other(), &MergePolicy::KeepFirst).unwrap();
+        assert_eq!(
+            merged.conflicts,
+            vec![Conflict {
+                object_type: "line",
+                id: "l1".to_string(),
+                new_id: None,
+            }]
+        );
+        let collections = merged.collections;
+        assert_eq!(collections.lines.len(), 1);
+        assert_eq!(collections.lines.get("l1").unwrap().name, "l1");
+        assert_eq!(collections.routes.get("r2").unwrap().line_id, "l1");
+    }
+
+    #[test]
+    fn prefix_policy_renames_the_other_object_and_its_references() {
+        let policy = MergePolicy::Prefix("o:".to_string());
+        let merged = merge(first(), other(), &policy).unwrap();
+        assert_eq!(
+            merged.conflicts,
+            vec![Conflict {
+                object_type: "line",
+                id: "l1".to_string(),
+                new_id: Some("o:l1".to_string()),
+            }]
+        );
+        let collections = merged.collections;
+        assert_eq!(collections.lines.len(), 2);
+        assert_eq!(collections.lines.get("o:l1").unwrap().name, "other");
+        assert_eq!(collections.routes.get("r1").unwrap().line_id, "l1");
+        assert_eq!(collections.routes.get("r2").unwrap().line_id, "o:l1");
+    }
+}
@@ -1,27 +1,863 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
 use std::path;
+#[cfg(not(target_arch = "wasm32"))]
+use std::thread;
 use csv;
 use serde;
 
 use collection::{Collection, Id};
+use error::{csv_error, io_error, Error, Result};
+use progress::{NoProgress, ProgressObserver, BATCH_SIZE};
+use sanitize::dangling as missing;
 use {Collections, PtObjects};
 
-fn make_collection<T>(path: &path::Path, file: &str) -> Collection<T>
+// Where the NTFS files are read from: a directory, or files already loaded
+// in memory (e.g. when there is no file system, as in a browser).
+#[derive(Clone, Copy)]
+enum Source<'a> {
+    Dir(&'a path::Path),
+    Memory(&'a HashMap<String, Vec<u8>>),
+}
+
+impl<'a> Source<'a> {
+    fn exists(&self, file: &str) -> bool {
+        match *self {
+            Source::Dir(path) => path.join(file).exists(),
+            Source::Memory(files) => files.contains_key(file),
+        }
+    }
+
+    fn size(&self, file: &str) -> u64 {
+        match *self {
+            Source::Dir(path) => fs::metadata(path.join(file)).map(|m| m.len()).unwrap_or(0),
+            Source::Memory(files) => files.get(file).map_or(0, |f| f.len() as u64),
+        }
+    }
+
+    // Used in error messages.
+    fn path(&self, file: &str) -> path::PathBuf {
+        match *self {
+            Source::Dir(path) => path.join(file),
+            Source::Memory(_) => path::PathBuf::from(file),
+        }
+    }
+
+    fn reader(&self, file: &str) -> Result<csv::Reader<Box<dyn io::Read + 'a>>> {
+        let rdr: Box<dyn io::Read + 'a> = match *self {
+            Source::Dir(path) => Box::new(
+                fs::File::open(path.join(file)).map_err(|e| io_error(&self.path(file), e))?,
+            ),
+            Source::Memory(files) => match files.get(file) {
+                Some(content) => Box::new(&content[..]),
+                None => {
+                    let e = io::Error::new(io::ErrorKind::NotFound, "file not provided");
+                    return Err(io_error(&self.path(file), e));
+                }
+            },
+        };
+        Ok(csv::Reader::from_reader(rdr))
+    }
+}
+
+fn check_cancelled(observer: &dyn ProgressObserver) -> Result<()> {
+    if observer.is_cancelled() {
+        Err(Error::Cancelled)
+    } else {
+        Ok(())
+    }
+}
+
+// Called before a file is read or written, so that small files also see a
+// cancellation.
+fn start_file(observer: &dyn ProgressObserver, file: &str) -> Result<()> {
+    check_cancelled(observer)?;
+    observer.file_started(file);
+    Ok(())
+}
+
+// Called after each record read or written; reports progress and checks for cancellation
+// once per batch.
+fn record_done(observer: &dyn ProgressObserver, file: &str, count: usize) -> Result<()> {
+    if count.is_multiple_of(BATCH_SIZE) {
+        check_cancelled(observer)?;
+        observer.records_processed(file, count);
+    }
+    Ok(())
+}
+
+// Line of each object in the file it was read from, in the order of the
+// objects read.
+type SourceLines = HashMap<&'static str, Vec<u64>>;
+
+fn record_line(record: &csv::StringRecord) -> u64 {
+    record.position().map_or(0, |p| p.line())
+}
+
+// The line of each record is kept in `lines`, as a record may span several
+// lines when a field is quoted.
+#[cfg(not(feature = "parallel"))]
+fn read_objects<T>(
+    source: Source,
+    file: &'static str,
+    observer: &dyn ProgressObserver,
+    lines: &mut SourceLines,
+) -> Result<Vec<T>>
 where
-    T: Id<T>,
+    T: Send,
     for<'de> T: serde::Deserialize<'de>,
 {
-    let mut lines_rdr = csv::Reader::from_path(path.join(file)).unwrap();
-    let lines = lines_rdr.deserialize().map(Result::unwrap).collect();
-    Collection::from_vec(lines)
+    start_file(observer, file)?;
+    let mut lines_rdr = source.reader(file)?;
+    let headers = lines_rdr
+        .headers()
+        .map_err(|e| csv_error(&source.path(file), e))?
+        .clone();
+    let mut objects = vec![];
+    let mut record_lines = vec![];
+    for record in lines_rdr.records() {
+        let record = record.map_err(|e| csv_error(&source.path(file), e))?;
+        record_lines.push(record_line(&record));
+        objects.push(
+            record
+                .deserialize(Some(&headers))
+                .map_err(|e| csv_error(&source.path(file), e))?,
+        );
+        record_done(observer, file, objects.len())?;
+    }
+    lines.insert(file, record_lines);
+    observer.records_processed(file, objects.len());
+    observer.file_finished(file);
+    Ok(objects)
 }
 
-pub fn read<P: AsRef<path::Path>>(path: P) -> PtObjects {
-    let path = path.as_ref();
+#[cfg(feature = "parallel")]
+fn read_objects<T>(
+    source: Source,
+    file: &'static str,
+    observer: &dyn ProgressObserver,
+    lines: &mut SourceLines,
+) -> Result<Vec<T>>
+where
+    T: Send,
+    for<'de> T: serde::Deserialize<'de>,
+{
+    use rayon::prelude::*;
+
+    start_file(observer, file)?;
+    let mut lines_rdr = source.reader(file)?;
+    let headers = lines_rdr
+        .headers()
+        .map_err(|e| csv_error(&source.path(file), e))?
+        .clone();
+    let mut records = vec![];
+    for record in lines_rdr.records() {
+        records.push(record.map_err(|e| csv_error(&source.path(file), e))?);
+        record_done(observer, file, records.len())?;
+    }
+    let objects = records
+        .par_iter()
+        .map(|record: &csv::StringRecord| {
+            record
+                .deserialize(Some(&headers))
+                .map_err(|e| csv_error(&source.path(file), e))
+        })
+        .collect::<Result<Vec<T>>>()?;
+    lines.insert(file, records.iter().map(record_line).collect());
+    observer.records_processed(file, objects.len());
+    observer.file_finished(file);
+    Ok(objects)
+}
+
+fn read_opt_objects<T>(
+    source: Source,
+    file: &'static str,
+    observer: &dyn ProgressObserver,
+    lines: &mut SourceLines,
+) -> Result<Vec<T>>
+where
+    T: Send,
+    for<'de> T: serde::Deserialize<'de>,
+{
+    if source.exists(file) {
+        read_objects(source, file, observer, lines)
+    } else {
+        Ok(vec![])
+    }
+}
+
+fn make_collection<T>(
+    source: Source,
+    file: &'static str,
+    observer: &dyn ProgressObserver,
+    lines: &mut SourceLines,
+) -> Result<Collection<T>>
+where
+    T: Id<T> + Send,
+    for<'de> T: serde::Deserialize<'de>,
+{
+    read_objects(source, file, observer, lines).map(Collection::from_vec)
+}
+
+fn make_opt_collection<T>(
+    source: Source,
+    file: &'static str,
+    observer: &dyn ProgressObserver,
+    lines: &mut SourceLines,
+) -> Result<Collection<T>>
+where
+    T: Id<T> + Send,
+    for<'de> T: serde::Deserialize<'de>,
+{
+    read_opt_objects(source, file, observer, lines).map(Collection::from_vec)
+}
+
+struct Budget {
+    remaining: Option<u64>,
+    skipped_files: Vec<String>,
+}
+
+impl Budget {
+    fn consume(&mut self, source: Source, file: &str) {
+        if let Some(ref mut remaining) = self.remaining {
+            *remaining = remaining.saturating_sub(source.size(file));
+        }
+    }
+
+    fn allows(&mut self, source: Source, file: &str) -> bool {
+        let size = source.size(file);
+        match self.remaining {
+            Some(remaining) if size > remaining => {
+                self.skipped_files.push(file.to_string());
+                false
+            }
+            _ => {
+                self.consume(source, file);
+                true
+            }
+        }
+    }
+}
+
+#[allow(clippy::field_reassign_with_default)]
+fn read_collections_within(
+    source: Source,
+    budget: &mut Budget,
+    observer: &dyn ProgressObserver,
+    lines: &mut SourceLines,
+) -> Result<Collections> {
     let mut collections = Collections::default();
-    collections.commercial_modes = make_collection(path, "commercial_modes.txt");
-    collections.lines = make_collection(path, "lines.txt");
-    collections.routes = make_collection(path, "routes.txt");
-    collections.vehicle_journeys = make_collection(path, "trips.txt");
-    collections.physical_modes = make_collection(path, "physical_modes.txt");
-    PtObjects::new(collections)
+    for file in &[
+        "networks.txt",
+        "commercial_modes.txt",
+        "lines.txt",
+        "routes.txt",
+        "trips.txt",
+        "physical_modes.txt",
+        "booking_rules.txt",
+        "vehicle_types.txt",
+        "on_demand_zones.txt",
+        "tickets.txt",
+        "ticket_prices.txt",
+        "ticket_uses.txt",
+        "ticket_use_perimeters.txt",
+    ] {
+        budget.consume(source, file);
+    }
+    collections.networks = make_collection(source, "networks.txt", observer, lines)?;
+    collections.commercial_modes =
+        make_collection(source, "commercial_modes.txt", observer, lines)?;
+    collections.lines = make_collection(source, "lines.txt", observer, lines)?;
+    collections.routes = make_collection(source, "routes.txt", observer, lines)?;
+    collections.vehicle_journeys = make_collection(source, "trips.txt", observer, lines)?;
+    collections.physical_modes = make_collection(source, "physical_modes.txt", observer, lines)?;
+    collections.booking_rules = make_opt_collection(source, "booking_rules.txt", observer, lines)?;
+    collections.vehicle_types = make_opt_collection(source, "vehicle_types.txt", observer, lines)?;
+    collections.on_demand_zones =
+        make_opt_collection(source, "on_demand_zones.txt", observer, lines)?;
+    collections.tickets = make_opt_collection(source, "tickets.txt", observer, lines)?;
+    collections.ticket_prices = read_opt_objects(source, "ticket_prices.txt", observer, lines)?;
+    collections.ticket_uses = make_opt_collection(source, "ticket_uses.txt", observer, lines)?;
+    collections.ticket_use_perimeters =
+        read_opt_objects(source, "ticket_use_perimeters.txt", observer, lines)?;
+    if budget.allows(source, "occupancies.txt") {
+        collections.occupancies = make_opt_collection(source, "occupancies.txt", observer, lines)?;
+    }
+    if budget.allows(source, "object_codes.txt") {
+        collections.object_codes = read_opt_objects(source, "object_codes.txt", observer, lines)?;
+    }
+    Ok(collections)
+}
+
+fn unlimited_budget() -> Budget {
+    Budget {
+        remaining: None,
+        skipped_files: vec![],
+    }
+}
+
+pub fn read_collections<P: AsRef<path::Path>>(path: P) -> Collections {
+    read_collections_with_progress(path, &NoProgress).unwrap()
+}
+
+pub fn read_collections_with_progress<P: AsRef<path::Path>>(
+    path: P,
+    observer: &dyn ProgressObserver,
+) -> Result<Collections> {
+    read_collections_within(
+        Source::Dir(path.as_ref()),
+        &mut unlimited_budget(),
+        observer,
+        &mut SourceLines::new(),
+    )
+}
+
+// The size of the files is used as an estimate of the memory needed to
+// load them. Mandatory files are always read; optional files that do not
+// fit in what remains of the budget are skipped and returned.
+pub fn read_collections_with_budget<P: AsRef<path::Path>>(
+    path: P,
+    memory_budget: u64,
+) -> Result<(Collections, Vec<String>)> {
+    let mut budget = Budget {
+        remaining: Some(memory_budget),
+        skipped_files: vec![],
+    };
+    let collections = read_collections_within(
+        Source::Dir(path.as_ref()),
+        &mut budget,
+        &NoProgress,
+        &mut SourceLines::new(),
+    )?;
+    Ok((collections, budget.skipped_files))
+}
+
+// `files` maps NTFS file names (e.g. "lines.txt") to their content.
+pub fn read_collections_from_memory(files: &HashMap<String, Vec<u8>>) -> Result<Collections> {
+    read_collections_within(
+        Source::Memory(files),
+        &mut unlimited_budget(),
+        &NoProgress,
+        &mut SourceLines::new(),
+    )
+}
+
+// Transformations applied right after reading.
+#[derive(Debug, Clone, Default)]
+pub struct ReadOptions {
+    // See `Collections::fix_line_colors`.
+    pub fix_line_colors: bool,
+}
+
+pub fn read_collections_with<P: AsRef<path::Path>>(
+    path: P,
+    options: &ReadOptions,
+) -> Result<Collections> {
+    let mut collections = read_collections_with_progress(path, &NoProgress)?;
+    if options.fix_line_colors {
+        collections.fix_line_colors();
+    }
+    Ok(collections)
+}
+
+pub fn read<P: AsRef<path::Path>>(path: P) -> PtObjects {
+    PtObjects::new(read_collections(path))
+}
+
+pub fn read_with_progress<P: AsRef<path::Path>>(
+    path: P,
+    observer: &dyn ProgressObserver,
+) -> Result<PtObjects> {
+    read_collections_with_progress(path, observer).map(PtObjects::new)
+}
+
+pub fn read_from_memory(files: &HashMap<String, Vec<u8>>) -> Result<PtObjects> {
+    read_collections_from_memory(files).map(PtObjects::new)
+}
+
+// `line` is only known when the references are checked right after reading,
+// see `read_collections_checked`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DanglingReference {
+    pub object_type: &'static str,
+    pub file: &'static str,
+    pub line: Option<u64>,
+    pub id: String,
+    pub reference: String,
+}
+
+impl fmt::Display for DanglingReference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: ", self.file, line)?,
+            None => write!(f, "{}: ", self.file)?,
+        }
+        write!(
+            f,
+            "{} has an invalid reference: {}",
+            self.id, self.reference
+        )
+    }
+}
+
+impl From<DanglingReference> for Error {
+    fn from(dangling: DanglingReference) -> Error {
+        Error::InvalidReference {
+            object_type: dangling.object_type,
+            id: dangling.id,
+            reference: dangling.reference,
+            file: Some(dangling.file),
+            line: dangling.line,
+        }
+    }
+}
+
+fn missing_opt<T: Id<T>>(
+    collection: &Collection<T>,
+    field: &str,
+    id: &Option<String>,
+) -> Option<String> {
+    id.as_ref().and_then(|id| missing(collection, field, id))
+}
+
+fn check_file<'a, T, I, F>(
+    lines: Option<&SourceLines>,
+    object_type: &'static str,
+    file: &'static str,
+    objects: I,
+    dangling: &mut Vec<DanglingReference>,
+    f: F,
+) where
+    T: 'a,
+    I: IntoIterator<Item = &'a T>,
+    F: Fn(&T) -> Option<(&str, String)>,
+{
+    let lines = lines.and_then(|lines| lines.get(file));
+    for (i, obj) in objects.into_iter().enumerate() {
+        if let Some((id, reference)) = f(obj) {
+            dangling.push(DanglingReference {
+                object_type,
+                file,
+                line: lines.map(|lines| lines[i]),
+                id: id.to_string(),
+                reference,
+            });
+        }
+    }
+}
+
+pub fn check_references(c: &Collections) -> Vec<DanglingReference> {
+    check_references_at(c, None)
+}
+
+fn check_references_at(c: &Collections, lines: Option<&SourceLines>) -> Vec<DanglingReference> {
+    let mut dangling = vec![];
+    check_file(
+        lines,
+        "line",
+        "lines.txt",
+        c.lines.iter().map(|(_, l)| l),
+        &mut dangling,
+        |l| {
+            missing(&c.networks, "network_id", &l.network_id)
+                .or_else(|| {
+                    missing(
+                        &c.commercial_modes,
+                        "commercial_mode_id",
+                        &l.commercial_mode_id,
+                    )
+                })
+                .or_else(|| missing_opt(&c.booking_rules, "booking_rule_id", &l.booking_rule_id))
+                .or_else(|| {
+                    missing_opt(
+                        &c.on_demand_zones,
+                        "on_demand_zone_id",
+                        &l.on_demand_zone_id,
+                    )
+                })
+                .map(|reason| (l.id.as_str(), reason))
+        },
+    );
+    check_file(
+        lines,
+        "on_demand_zone",
+        "on_demand_zones.txt",
+        c.on_demand_zones.iter().map(|(_, z)| z),
+        &mut dangling,
+        |z| {
+            missing_opt(&c.booking_rules, "booking_rule_id", &z.booking_rule_id)
+                .map(|reason| (z.id.as_str(), reason))
+        },
+    );
+    check_file(
+        lines,
+        "route",
+        "routes.txt",
+        c.routes.iter().map(|(_, r)| r),
+        &mut dangling,
+        |r| missing(&c.lines, "line_id", &r.line_id).map(|reason| (r.id.as_str(), reason)),
+    );
+    check_file(
+        lines,
+        "vehicle_journey",
+        "trips.txt",
+        c.vehicle_journeys.iter().map(|(_, vj)| vj),
+        &mut dangling,
+        |vj| {
+            missing(&c.routes, "route_id", &vj.route_id)
+                .or_else(|| missing(&c.physical_modes, "physical_mode_id", &vj.physical_mode_id))
+                .or_else(|| missing_opt(&c.booking_rules, "booking_rule_id", &vj.booking_rule_id))
+                .or_else(|| missing_opt(&c.vehicle_types, "vehicle_type_id", &vj.vehicle_type_id))
+                .or_else(|| {
+                    missing_opt(
+                        &c.on_demand_zones,
+                        "on_demand_zone_id",
+                        &vj.on_demand_zone_id,
+                    )
+                })
+                .map(|reason| (vj.id.as_str(), reason))
+        },
+    );
+    check_file(
+        lines,
+        "occupancy",
+        "occupancies.txt",
+        c.occupancies.iter().map(|(_, o)| o),
+        &mut dangling,
+        |o| {
+            missing(&c.vehicle_journeys, "trip_id", &o.vehicle_journey_id)
+                .map(|reason| (o.id.as_str(), reason))
+        },
+    );
+    check_file(
+        lines,
+        "object_code",
+        "object_codes.txt",
+        &c.object_codes,
+        &mut dangling,
+        |code| {
+            c.missing_object(code.object_type, &code.object_id)
+                .map(|reason| (code.object_id.as_str(), reason))
+        },
+    );
+    check_file(
+        lines,
+        "ticket_price",
+        "ticket_prices.txt",
+        &c.ticket_prices,
+        &mut dangling,
+        |p| {
+            missing(&c.tickets, "ticket_id", &p.ticket_id)
+                .map(|reason| (p.ticket_id.as_str(), reason))
+        },
+    );
+    check_file(
+        lines,
+        "ticket_use",
+        "ticket_uses.txt",
+        c.ticket_uses.iter().map(|(_, u)| u),
+        &mut dangling,
+        |u| missing(&c.tickets, "ticket_id", &u.ticket_id).map(|reason| (u.id.as_str(), reason)),
+    );
+    check_file(
+        lines,
+        "ticket_use_perimeter",
+        "ticket_use_perimeters.txt",
+        &c.ticket_use_perimeters,
+        &mut dangling,
+        |p| {
+            missing(&c.ticket_uses, "ticket_use_id", &p.ticket_use_id)
+                .or_else(|| c.missing_object(p.object_type, &p.object_id))
+                .map(|reason| (p.ticket_use_id.as_str(), reason))
+        },
+    );
+    dangling
+}
+
+fn read_checked(source: Source) -> Result<(Collections, Vec<DanglingReference>)> {
+    let mut lines = SourceLines::new();
+    let collections =
+        read_collections_within(source, &mut unlimited_budget(), &NoProgress, &mut lines)?;
+    let dangling = check_references_at(&collections, Some(&lines));
+    Ok((collections, dangling))
+}
+
+// Same as `check_references` on the collections read, with the line of each
+// dangling reference.
+pub fn read_collections_checked<P: AsRef<path::Path>>(
+    path: P,
+) -> Result<(Collections, Vec<DanglingReference>)> {
+    read_checked(Source::Dir(path.as_ref()))
+}
+
+// Fails on the first dangling reference instead of letting it panic later
+// when the relations are built. Use `read_collections_checked` to get all of
+// them.
+pub fn read_collections_strict<P: AsRef<path::Path>>(path: P) -> Result<Collections> {
+    let (collections, dangling) = read_collections_checked(path)?;
+    match dangling.into_iter().next() {
+        Some(dangling) => Err(dangling.into()),
+        None => Ok(collections),
+    }
+}
+
+const WRITE_BUFFER_CAPACITY: usize = 1 << 20;
+
+fn write_objects<'a, T, I>(
+    path: &path::Path,
+    file: &str,
+    objects: I,
+    observer: &dyn ProgressObserver,
+) -> Result<()>
+where
+    T: serde::Serialize + 'a,
+    I: IntoIterator<Item = &'a T>,
+{
+    start_file(observer, file)?;
+    let path = path.join(file);
+    let mut wtr = csv::WriterBuilder::new()
+        .buffer_capacity(WRITE_BUFFER_CAPACITY)
+        .from_path(&path)
+        .map_err(|e| csv_error(&path, e))?;
+    let mut count = 0;
+    for obj in objects {
+        wtr.serialize(obj).map_err(|e| csv_error(&path, e))?;
+        count += 1;
+        record_done(observer, file, count)?;
+    }
+    wtr.flush().map_err(|e| io_error(&path, e))?;
+    observer.records_processed(file, count);
+    observer.file_finished(file);
+    Ok(())
+}
+
+fn write_collection<T>(
+    path: &path::Path,
+    file: &str,
+    collection: &Collection<T>,
+    observer: &dyn ProgressObserver,
+) -> Result<()>
+where
+    T: serde::Serialize,
+{
+    write_objects(path, file, collection.iter().map(|(_, obj)| obj), observer)
+}
+
+fn write_opt_collection<T>(
+    path: &path::Path,
+    file: &str,
+    collection: &Collection<T>,
+    observer: &dyn ProgressObserver,
+) -> Result<()>
+where
+    T: serde::Serialize,
+{
+    if collection.is_empty() {
+        return Ok(());
+    }
+    write_collection(path, file, collection, observer)
+}
+
+type WriteJob<'a> = Box<dyn FnOnce() -> Result<()> + Send + 'a>;
+
+fn write_jobs<'a>(
+    path: &'a path::Path,
+    pt_objects: &'a PtObjects,
+    obs: &'a dyn ProgressObserver,
+) -> Vec<WriteJob<'a>> {
+    let mut jobs: Vec<WriteJob> = vec![
+        Box::new(move || write_collection(path, "networks.txt", &pt_objects.networks, obs)),
+        Box::new(move || {
+            write_collection(
+                path,
+                "commercial_modes.txt",
+                &pt_objects.commercial_modes,
+                obs,
+            )
+        }),
+        Box::new(move || write_collection(path, "lines.txt", &pt_objects.lines, obs)),
+        Box::new(move || write_collection(path, "routes.txt", &pt_objects.routes, obs)),
+        Box::new(move || write_collection(path, "trips.txt", &pt_objects.vehicle_journeys, obs)),
+        Box::new(move || {
+            write_collection(path, "physical_modes.txt", &pt_objects.physical_modes, obs)
+        }),
+        Box::new(move || {
+            write_opt_collection(path, "occupancies.txt", &pt_objects.occupancies, obs)
+        }),
+        Box::new(move || {
+            write_opt_collection(path, "booking_rules.txt", &pt_objects.booking_rules, obs)
+        }),
+        Box::new(move || {
+            write_opt_collection(path, "vehicle_types.txt", &pt_objects.vehicle_types, obs)
+        }),
+        Box::new(move || {
+            write_opt_collection(
+                path,
+                "on_demand_zones.txt",
+                &pt_objects.on_demand_zones,
+                obs,
+            )
+        }),
+        Box::new(move || write_opt_collection(path, "tickets.txt", &pt_objects.tickets, obs)),
+        Box::new(move || {
+            write_opt_collection(path, "ticket_uses.txt", &pt_objects.ticket_uses, obs)
+        }),
+    ];
+    if !pt_objects.object_codes.is_empty() {
+        jobs.push(Box::new(move || {
+            write_objects(path, "object_codes.txt", &pt_objects.object_codes, obs)
+        }));
+    }
+    if !pt_objects.ticket_prices.is_empty() {
+        jobs.push(Box::new(move || {
+            write_objects(path, "ticket_prices.txt", &pt_objects.ticket_prices, obs)
+        }));
+    }
+    if !pt_objects.ticket_use_perimeters.is_empty() {
+        jobs.push(Box::new(move || {
+            write_objects(
+                path,
+                "ticket_use_perimeters.txt",
+                &pt_objects.ticket_use_perimeters,
+                obs,
+            )
+        }));
+    }
+    jobs
+}
+
+pub fn write<P: AsRef<path::Path>>(path: P, pt_objects: &PtObjects) {
+    write_with_progress(path, pt_objects, &NoProgress).unwrap();
+}
+
+// Each file is written by its own thread; files are independent so the
+// content does not depend on scheduling. On cancellation, the files
+// already started are left partially written.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write_with_progress<P: AsRef<path::Path>>(
+    path: P,
+    pt_objects: &PtObjects,
+    observer: &dyn ProgressObserver,
+) -> Result<()> {
+    let jobs = write_jobs(path.as_ref(), pt_objects, observer);
+    thread::scope(|s| {
+        let handles: Vec<_> = jobs.into_iter().map(|job| s.spawn(job)).collect();
+        // every thread is joined before returning the first error, or the
+        // scope would panic again on a thread left unjoined
+        let results: Vec<_> = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap_or(Err(Error::WriterPanicked)))
+            .collect();
+        results.into_iter().collect()
+    })
+}
+
+// Threads cannot be spawned on wasm32, the files are written one by one.
+#[cfg(target_arch = "wasm32")]
+pub fn write_with_progress<P: AsRef<path::Path>>(
+    path: P,
+    pt_objects: &PtObjects,
+    observer: &dyn ProgressObserver,
+) -> Result<()> {
+    for job in write_jobs(path.as_ref(), pt_objects, observer) {
+        job()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use progress::CancellationToken;
+
+    fn minimal_files() -> HashMap<String, Vec<u8>> {
+        let mut files = HashMap::new();
+        for &(file, content) in &[
+            (
+                "networks.txt",
+                "network_id,network_name,network_timezone\nN1,N,UTC\n",
+            ),
+            (
+                "commercial_modes.txt",
+                "commercial_mode_id,commercial_mode_name\nB,B\n",
+            ),
+            (
+                "lines.txt",
+                "line_id,line_name,network_id,commercial_mode_id\nL1,L,N1,B\n",
+            ),
+            ("routes.txt", "route_id,route_name,line_id\nR1,R,L1\n"),
+            ("trips.txt", "trip_id,route_id,physical_mode_id\nT1,R1,B\n"),
+            (
+                "physical_modes.txt",
+                "physical_mode_id,physical_mode_name\nB,B\n",
+            ),
+        ] {
+            files.insert(file.to_string(), content.as_bytes().to_vec());
+        }
+        files
+    }
+
+    #[test]
+    fn read_checks_cancellation_before_the_first_batch() {
+        let files = minimal_files();
+        let token = CancellationToken::new();
+        let read = |token: &CancellationToken| {
+            read_collections_within(
+                Source::Memory(&files),
+                &mut unlimited_budget(),
+                token,
+                &mut SourceLines::new(),
+            )
+        };
+        assert_eq!(read(&token).unwrap().lines.len(), 1);
+        token.cancel();
+        assert!(matches!(read(&token), Err(Error::Cancelled)));
+    }
+
+    #[test]
+    fn read_from_memory_returns_malformed_csv_errors() {
+        let mut files = minimal_files();
+        files.insert(
+            "routes.txt".to_string(),
+            b"route_id,route_name,line_id\nR1,R\n".to_vec(),
+        );
+        match read_from_memory(&files) {
+            Err(Error::Csv { path, .. }) => assert_eq!(path, "routes.txt"),
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("malformed routes.txt was read"),
+        }
+        let mut files = minimal_files();
+        files.remove("trips.txt");
+        assert!(matches!(read_from_memory(&files), Err(Error::Io { .. })));
+    }
+
+    #[test]
+    fn dangling_reference_line_accounts_for_multiline_records() {
+        let mut files = minimal_files();
+        files.insert(
+            "lines.txt".to_string(),
+            b"line_id,line_name,network_id,commercial_mode_id\nL1,\"L\n1\",N1,B\nL2,L,N2,B\n"
+                .to_vec(),
+        );
+        let (_, dangling) = read_checked(Source::Memory(&files)).unwrap();
+        assert_eq!(dangling.len(), 1);
+        assert_eq!(dangling[0].id, "L2");
+        assert_eq!(dangling[0].line, Some(4));
+    }
+
+    #[test]
+    fn write_checks_cancellation_before_the_first_batch() {
+        let pt_objects = read_from_memory(&minimal_files()).unwrap();
+        let dir = std::env::temp_dir().join(format!("navitia_model_cancel_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let token = CancellationToken::new();
+        token.cancel();
+        let res = write_with_progress(&dir, &pt_objects, &token);
+        let written = fs::read_dir(&dir).unwrap().count();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(matches!(res, Err(Error::Cancelled)));
+        assert_eq!(written, 0);
+    }
 }
@@ -1,6 +1,6 @@
 use collection::Id;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct CommercialMode {
     #[serde(rename = "commercial_mode_id")] pub id: String,
     #[serde(rename = "commercial_mode_name")] pub name: String,
@@ -11,7 +11,7 @@ impl Id<CommercialMode> for CommercialMode {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct PhysicalMode {
     #[serde(rename = "physical_mode_id")] pub id: String,
     #[serde(rename = "physical_mode_name")] pub name: String,
@@ -22,7 +22,7 @@ impl Id<PhysicalMode> for PhysicalMode {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct Network {
     #[serde(rename = "network_id")] pub id: String,
     #[serde(rename = "network_name")] pub name: String,
@@ -34,30 +34,64 @@ impl Id<Network> for Network {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+// Colors are written as 6 hexadecimal digits, without a leading '#'.
+pub fn is_valid_color(color: &str) -> bool {
+    color.len() == 6 && color.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+// Black or white, whichever contrasts best with the given background
+// according to its relative luminance (WCAG 2.0).
+pub fn readable_text_color(color: &str) -> Option<&'static str> {
+    if !is_valid_color(color) {
+        return None;
+    }
+    let channel = |i: usize| {
+        let c = f64::from(u8::from_str_radix(&color[i..i + 2], 16).unwrap()) / 255.;
+        if c <= 0.03928 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let luminance = 0.2126 * channel(0) + 0.7152 * channel(2) + 0.0722 * channel(4);
+    // (L + 0.05) / 0.05 > 1.05 / (L + 0.05)
+    if luminance > 0.179 {
+        Some("000000")
+    } else {
+        Some("FFFFFF")
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct Line {
     #[serde(rename = "line_id")] pub id: String,
     #[serde(rename = "line_name")] pub name: String,
     pub network_id: String,
     pub commercial_mode_id: String,
+    #[serde(default)] pub booking_rule_id: Option<String>,
+    #[serde(rename = "line_color", default)] pub color: Option<String>,
+    #[serde(rename = "line_text_color", default)] pub text_color: Option<String>,
+    #[serde(default)] pub on_demand_zone_id: Option<String>,
 }
 impl Id<Line> for Line {
     fn id(&self) -> &str {
         &self.id
     }
 }
+#[allow(clippy::misnamed_getters)]
 impl Id<Network> for Line {
     fn id(&self) -> &str {
         &self.network_id
     }
 }
+#[allow(clippy::misnamed_getters)]
 impl Id<CommercialMode> for Line {
     fn id(&self) -> &str {
         &self.commercial_mode_id
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct Route {
     #[serde(rename = "route_id")] pub id: String,
     #[serde(rename = "route_name")] pub name: String,
@@ -68,30 +102,253 @@ impl Id<Route> for Route {
         &self.id
     }
 }
+#[allow(clippy::misnamed_getters)]
 impl Id<Line> for Route {
     fn id(&self) -> &str {
         &self.line_id
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Availability {
+    #[serde(rename = "0")] InformationNotAvailable,
+    #[serde(rename = "1")] Available,
+    #[serde(rename = "2")] NotAvailable,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Propulsion {
+    Diesel,
+    Electric,
+    Hybrid,
+    Hydrogen,
+    NaturalGas,
+    Other,
+}
+
+// Floor height is in meters above the road or rail.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct VehicleType {
+    #[serde(rename = "vehicle_type_id")] pub id: String,
+    #[serde(rename = "vehicle_type_name")] pub name: String,
+    #[serde(default)] pub seated_capacity: Option<u32>,
+    #[serde(default)] pub standing_capacity: Option<u32>,
+    #[serde(default)] pub floor_height: Option<f64>,
+    #[serde(default)] pub bike_spaces: Option<u32>,
+    #[serde(default)] pub propulsion: Option<Propulsion>,
+}
+impl Id<VehicleType> for VehicleType {
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct VehicleJourney {
     #[serde(rename = "trip_id")] pub id: String,
     pub route_id: String,
     pub physical_mode_id: String,
+    #[serde(default)] pub booking_rule_id: Option<String>,
+    #[serde(default)] pub wheelchair_accessible: Option<Availability>,
+    #[serde(default)] pub bikes_allowed: Option<Availability>,
+    #[serde(default)] pub stroller_allowed: Option<Availability>,
+    #[serde(default)] pub luggage_allowed: Option<Availability>,
+    #[serde(default)] pub vehicle_type_id: Option<String>,
+    #[serde(default)] pub on_demand_zone_id: Option<String>,
 }
 impl Id<VehicleJourney> for VehicleJourney {
     fn id(&self) -> &str {
         &self.id
     }
 }
+#[allow(clippy::misnamed_getters)]
 impl Id<Route> for VehicleJourney {
     fn id(&self) -> &str {
         &self.route_id
     }
 }
+#[allow(clippy::misnamed_getters)]
 impl Id<PhysicalMode> for VehicleJourney {
     fn id(&self) -> &str {
         &self.physical_mode_id
     }
 }
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OccupancyStatus {
+    #[serde(rename = "0")] Empty,
+    #[serde(rename = "1")] ManySeatsAvailable,
+    #[serde(rename = "2")] FewSeatsAvailable,
+    #[serde(rename = "3")] StandingRoomOnly,
+    #[serde(rename = "4")] CrushedStandingRoomOnly,
+    #[serde(rename = "5")] Full,
+    #[serde(rename = "6")] NotAcceptingPassengers,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Occupancy {
+    #[serde(rename = "occupancy_id")] pub id: String,
+    #[serde(rename = "trip_id")] pub vehicle_journey_id: String,
+    pub stop_sequence: Option<u32>,
+    #[serde(rename = "occupancy")] pub status: OccupancyStatus,
+}
+impl Id<Occupancy> for Occupancy {
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+#[allow(clippy::misnamed_getters)]
+impl Id<VehicleJourney> for Occupancy {
+    fn id(&self) -> &str {
+        &self.vehicle_journey_id
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PickupDropOffType {
+    #[serde(rename = "0")] Regular,
+    #[serde(rename = "1")] NotAvailable,
+    #[serde(rename = "2")] MustPhone,
+    #[serde(rename = "3")] MustCoordinateWithDriver,
+}
+
+// An area served on demand. The shape is a WKT polygon or multipolygon in
+// WGS84 coordinates; booking contacts come from the booking rule.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct OnDemandZone {
+    #[serde(rename = "on_demand_zone_id")] pub id: String,
+    #[serde(rename = "on_demand_zone_name")] pub name: String,
+    #[serde(rename = "on_demand_zone_shape")] pub shape: String,
+    #[serde(default)] pub pickup_type: Option<PickupDropOffType>,
+    #[serde(default)] pub drop_off_type: Option<PickupDropOffType>,
+    #[serde(default)] pub booking_rule_id: Option<String>,
+}
+impl Id<OnDemandZone> for OnDemandZone {
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct BookingRule {
+    #[serde(rename = "booking_rule_id")] pub id: String,
+    #[serde(rename = "booking_rule_name")] pub name: String,
+    #[serde(rename = "phone_number")] pub phone: Option<String>,
+    #[serde(rename = "booking_url")] pub url: Option<String>,
+    #[serde(rename = "prior_notice_duration_min")] pub deadline: Option<u32>,
+    #[serde(rename = "message")] pub conditions: Option<String>,
+}
+impl Id<BookingRule> for BookingRule {
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ObjectType {
+    #[serde(rename = "network")] Network,
+    #[serde(rename = "line")] Line,
+    #[serde(rename = "route")] Route,
+    #[serde(rename = "trip")] VehicleJourney,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ObjectCode {
+    pub object_type: ObjectType,
+    pub object_id: String,
+    pub object_system: String,
+    pub object_code: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Ticket {
+    #[serde(rename = "ticket_id")] pub id: String,
+    #[serde(rename = "ticket_name")] pub name: String,
+    #[serde(rename = "ticket_comment", default)] pub comment: Option<String>,
+}
+impl Id<Ticket> for Ticket {
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+// Validity dates are written as YYYYMMDD.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TicketPrice {
+    pub ticket_id: String,
+    #[serde(rename = "ticket_price")] pub price: f64,
+    #[serde(rename = "ticket_currency")] pub currency: String,
+    #[serde(rename = "ticket_validity_start")] pub validity_start: String,
+    #[serde(rename = "ticket_validity_end")] pub validity_end: String,
+}
+
+// Time limits are in seconds.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct TicketUse {
+    #[serde(rename = "ticket_use_id")] pub id: String,
+    pub ticket_id: String,
+    #[serde(default)] pub max_transfers: Option<u32>,
+    #[serde(default)] pub boarding_time_limit: Option<u32>,
+    #[serde(default)] pub alighting_time_limit: Option<u32>,
+}
+impl Id<TicketUse> for TicketUse {
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+#[allow(clippy::misnamed_getters)]
+impl Id<Ticket> for TicketUse {
+    fn id(&self) -> &str {
+        &self.ticket_id
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PerimeterAction {
+    #[serde(rename = "1")] Included = 1,
+    #[serde(rename = "2")] Excluded = 2,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TicketUsePerimeter {
+    pub ticket_use_id: String,
+    pub object_type: ObjectType,
+    pub object_id: String,
+    pub perimeter_action: PerimeterAction,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color_validity() {
+        assert!(is_valid_color("00A1E4"));
+        assert!(is_valid_color("ff00aa"));
+        for color in &["", "FFF", "#FFFFFF", "FFFFFFF", "GGGGGG", "ééé"] {
+            assert!(!is_valid_color(color), "{} should be invalid", color);
+        }
+    }
+
+    #[test]
+    fn readable_text_color_around_the_threshold() {
+        assert_eq!(readable_text_color("FFFFFF"), Some("000000"));
+        assert_eq!(readable_text_color("000000"), Some("FFFFFF"));
+        // Greys of luminance 0.1779 and 0.1812.
+        assert_eq!(readable_text_color("757575"), Some("FFFFFF"));
+        assert_eq!(readable_text_color("767676"), Some("000000"));
+        // Channels are weighted: pure red (0.2126) is above the threshold, pure
+        // blue (0.0722) below it.
+        assert_eq!(readable_text_color("FF0000"), Some("000000"));
+        assert_eq!(readable_text_color("0000FF"), Some("FFFFFF"));
+        assert_eq!(readable_text_color("00ff00"), Some("000000"));
+    }
+
+    #[test]
+    fn readable_text_color_of_invalid_colors() {
+        assert_eq!(readable_text_color("#FFFFFF"), None);
+        assert_eq!(readable_text_color("GGGGGG"), None);
+        assert_eq!(readable_text_color("ééé"), None);
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::Write;
+use std::path;
+use serde_json;
+
+use apply_rules;
+use error::{io_error, json_error, Result};
+use merge::{self, Conflict, MergePolicy};
+use prefix::{self, PrefixConfiguration};
+use progress::NoProgress;
+use sanitize::{SanitizeConfig, SanitizeReport};
+use {ntfs, stats, Collections, PtObjects};
+
+fn default_separator() -> String {
+    ":".to_string()
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Step {
+    Merge {
+        input: String,
+        #[serde(default)]
+        on_conflict: MergePolicy,
+    },
+    ApplyRules {
+        #[serde(default)]
+        complementary_code_rules: Vec<String>,
+        #[serde(default)]
+        property_rules: Vec<String>,
+        networks_consolidation: Option<String>,
+    },
+    // Keeps the listed networks and everything that depends on them.
+    FilterNetworks {
+        networks: Vec<String>,
+    },
+    Sanitize {
+        #[serde(default)]
+        fail_on_dangling_references: bool,
+        #[serde(default)]
+        fix_line_colors: bool,
+    },
+    // Clears invalid line colors and computes missing text colors.
+    FixLineColors,
+    AddPrefix {
+        prefix: String,
+        #[serde(default = "default_separator")]
+        separator: String,
+    },
+}
+
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Output {
+    pub ntfs: Option<String>,
+    pub stats_json: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    pub input: String,
+    #[serde(default)]
+    pub steps: Vec<Step>,
+    #[serde(default)]
+    pub output: Output,
+}
+
+impl Config {
+    pub fn from_path<P: AsRef<path::Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file = fs::File::open(path).map_err(|e| io_error(path, e))?;
+        serde_json::from_reader(file).map_err(|e| json_error(path, e))
+    }
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct Report {
+    pub merge_conflicts: Vec<Conflict>,
+    pub rules: Vec<apply_rules::Report>,
+    pub sanitize: Vec<SanitizeReport>,
+}
+
+fn filter_networks(collections: &mut Collections, networks: &[String]) -> Result<SanitizeReport> {
+    let networks: HashSet<&str> = networks.iter().map(|n| n.as_str()).collect();
+    collections
+        .networks
+        .retain(|n| networks.contains(n.id.as_str()));
+    collections.sanitize(&SanitizeConfig::default())
+}
+
+fn run_step(collections: Collections, step: &Step, report: &mut Report) -> Result<Collections> {
+    let mut collections = collections;
+    match *step {
+        Step::Merge {
+            ref input,
+            ref on_conflict,
+        } => {
+            let other = ntfs::read_collections_with_progress(input, &NoProgress)?;
+            let merged = merge::merge(collections, other, on_conflict)?;
+            report.merge_conflicts.extend(merged.conflicts);
+            return Ok(merged.collections);
+        }
+        Step::ApplyRules {
+            ref complementary_code_rules,
+            ref property_rules,
+            ref networks_consolidation,
+        } => {
+            report.rules.push(apply_rules::apply_rules(
+                &mut collections,
+                complementary_code_rules,
+                property_rules,
+                networks_consolidation.clone(),
+            )?);
+        }
+        Step::FilterNetworks { ref networks } => {
+            report
+                .sanitize
+                .push(filter_networks(&mut collections, networks)?);
+        }
+        Step::Sanitize {
+            fail_on_dangling_references,
+            fix_line_colors,
+        } => {
+            let config = SanitizeConfig {
+                fail_on_dangling_references,
+                fix_line_colors,
+                ..SanitizeConfig::default()
+            };
+            report.sanitize.push(collections.sanitize(&config)?);
+        }
+        Step::FixLineColors => {
+            report.sanitize.push(collections.fix_line_colors());
+        }
+        Step::AddPrefix {
+            ref prefix,
+            ref separator,
+        } => {
+            let config = PrefixConfiguration::new(prefix).separator(separator);
+            prefix::add_prefix(&mut collections, &config);
+        }
+    }
+    Ok(collections)
+}
+
+// Steps are run in order on the model read from `input`, then the outputs
+// are written. Nothing is written if a step fails, and the stats file is
+// created before the NTFS is written so that an invalid path fails early.
+pub fn run(config: &Config) -> Result<Report> {
+    let mut report = Report::default();
+    let mut collections = ntfs::read_collections_with_progress(&config.input, &NoProgress)?;
+    for step in &config.steps {
+        collections = run_step(collections, step, &mut report)?;
+    }
+    // The relations needed by the stats cannot be built on dangling references.
+    if let Some(dangling) = ntfs::check_references(&collections).into_iter().next() {
+        return Err(dangling.into());
+    }
+    let pt_objects = PtObjects::new(collections);
+    let stats_json = match config.output.stats_json {
+        Some(ref path) => {
+            let path = path::Path::new(path);
+            let json = serde_json::to_vec_pretty(&stats::compute(&pt_objects))
+                .map_err(|e| json_error(path, e))?;
+            let file = fs::File::create(path).map_err(|e| io_error(path, e))?;
+            Some((path, file, json))
+        }
+        None => None,
+    };
+    if let Some(ref path) = config.output.ntfs {
+        fs::create_dir_all(path).map_err(|e| io_error(path::Path::new(path), e))?;
+        ntfs::write_with_progress(path, &pt_objects, &NoProgress)?;
+    }
+    if let Some((path, mut file, json)) = stats_json {
+        file.write_all(&json).map_err(|e| io_error(path, e))?;
+    }
+    Ok(report)
+}
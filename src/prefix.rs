@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+use std::mem;
+
+use collection::{Collection, Id};
+use objects::ObjectType;
+use Collections;
+
+// The types whose ids can be prefixed: the object types plus the objects
+// that codes and perimeters cannot refer to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PrefixedType {
+    Network,
+    Line,
+    Route,
+    VehicleJourney,
+    BookingRule,
+    Occupancy,
+    VehicleType,
+    OnDemandZone,
+    Ticket,
+    TicketUse,
+}
+
+impl From<ObjectType> for PrefixedType {
+    fn from(object_type: ObjectType) -> Self {
+        match object_type {
+            ObjectType::Network => PrefixedType::Network,
+            ObjectType::Line => PrefixedType::Line,
+            ObjectType::Route => PrefixedType::Route,
+            ObjectType::VehicleJourney => PrefixedType::VehicleJourney,
+        }
+    }
+}
+
+const ALL_PREFIXED_TYPES: [PrefixedType; 10] = [
+    PrefixedType::Network,
+    PrefixedType::Line,
+    PrefixedType::Route,
+    PrefixedType::VehicleJourney,
+    PrefixedType::BookingRule,
+    PrefixedType::Occupancy,
+    PrefixedType::VehicleType,
+    PrefixedType::OnDemandZone,
+    PrefixedType::Ticket,
+    PrefixedType::TicketUse,
+];
+
+#[derive(Debug, Clone)]
+pub struct PrefixConfiguration {
+    separator: String,
+    prefixes: HashMap<PrefixedType, String>,
+}
+
+impl PrefixConfiguration {
+    pub fn new(prefix: &str) -> Self {
+        PrefixConfiguration {
+            separator: ":".to_string(),
+            prefixes: ALL_PREFIXED_TYPES
+                .iter()
+                .map(|&t| (t, prefix.to_string()))
+                .collect(),
+        }
+    }
+
+    pub fn separator(mut self, separator: &str) -> Self {
+        self.separator = separator.to_string();
+        self
+    }
+
+    pub fn prefix<T: Into<PrefixedType>>(mut self, object_type: T, prefix: &str) -> Self {
+        self.prefixes.insert(object_type.into(), prefix.to_string());
+        self
+    }
+
+    pub fn exclude<T: Into<PrefixedType>>(mut self, object_type: T) -> Self {
+        self.prefixes.remove(&object_type.into());
+        self
+    }
+
+    fn full_prefix(&self, object_type: PrefixedType) -> Option<String> {
+        self.prefixes
+            .get(&object_type)
+            .map(|prefix| format!("{}{}", prefix, self.separator))
+    }
+}
+
+fn update_collection<T, F>(collection: &mut Collection<T>, f: F)
+where
+    T: Id<T>,
+    F: Fn(&mut T),
+{
+    let mut objects = mem::take(collection).into_vec();
+    for obj in &mut objects {
+        f(obj);
+    }
+    *collection = Collection::from_vec(objects);
+}
+
+fn rename_opt<F>(rename: &F, object_type: PrefixedType, id: &mut Option<String>)
+where
+    F: Fn(PrefixedType, &mut String),
+{
+    if let Some(ref mut id) = *id {
+        rename(object_type, id);
+    }
+}
+
+fn rename_ids<F>(collections: &mut Collections, rename: F)
+where
+    F: Fn(PrefixedType, &mut String),
+{
+    update_collection(&mut collections.networks, |n| {
+        rename(PrefixedType::Network, &mut n.id)
+    });
+    update_collection(&mut collections.booking_rules, |b| {
+        rename(PrefixedType::BookingRule, &mut b.id)
+    });
+    update_collection(&mut collections.vehicle_types, |t| {
+        rename(PrefixedType::VehicleType, &mut t.id)
+    });
+    update_collection(&mut collections.on_demand_zones, |z| {
+        rename(PrefixedType::OnDemandZone, &mut z.id);
+        rename_opt(&rename, PrefixedType::BookingRule, &mut z.booking_rule_id);
+    });
+    update_collection(&mut collections.lines, |l| {
+        rename(PrefixedType::Line, &mut l.id);
+        rename(PrefixedType::Network, &mut l.network_id);
+        rename_opt(&rename, PrefixedType::BookingRule, &mut l.booking_rule_id);
+        rename_opt(&rename, PrefixedType::OnDemandZone, &mut l.on_demand_zone_id);
+    });
+    update_collection(&mut collections.routes, |r| {
+        rename(PrefixedType::Route, &mut r.id);
+        rename(PrefixedType::Line, &mut r.line_id);
+    });
+    update_collection(&mut collections.vehicle_journeys, |vj| {
+        rename(PrefixedType::VehicleJourney, &mut vj.id);
+        rename(PrefixedType::Route, &mut vj.route_id);
+        rename_opt(&rename, PrefixedType::BookingRule, &mut vj.booking_rule_id);
+        rename_opt(&rename, PrefixedType::VehicleType, &mut vj.vehicle_type_id);
+        rename_opt(&rename, PrefixedType::OnDemandZone, &mut vj.on_demand_zone_id);
+    });
+    update_collection(&mut collections.occupancies, |o| {
+        rename(PrefixedType::Occupancy, &mut o.id);
+        rename(PrefixedType::VehicleJourney, &mut o.vehicle_journey_id);
+    });
+    for code in &mut collections.object_codes {
+        rename(code.object_type.into(), &mut code.object_id);
+    }
+    update_collection(&mut collections.tickets, |t| {
+        rename(PrefixedType::Ticket, &mut t.id)
+    });
+    for price in &mut collections.ticket_prices {
+        rename(PrefixedType::Ticket, &mut price.ticket_id);
+    }
+    update_collection(&mut collections.ticket_uses, |u| {
+        rename(PrefixedType::TicketUse, &mut u.id);
+        rename(PrefixedType::Ticket, &mut u.ticket_id);
+    });
+    for perimeter in &mut collections.ticket_use_perimeters {
+        rename(PrefixedType::TicketUse, &mut perimeter.ticket_use_id);
+        rename(perimeter.object_type.into(), &mut perimeter.object_id);
+    }
+}
+
+pub fn add_prefix(collections: &mut Collections, config: &PrefixConfiguration) {
+    rename_ids(collections, |object_type, id| {
+        if let Some(prefix) = config.full_prefix(object_type) {
+            *id = format!("{}{}", prefix, id);
+        }
+    });
+}
+
+pub fn remove_prefix(collections: &mut Collections, config: &PrefixConfiguration) {
+    rename_ids(collections, |object_type, id| {
+        if let Some(prefix) = config.full_prefix(object_type) {
+            if id.starts_with(&prefix) {
+                *id = id[prefix.len()..].to_string();
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builder::ModelBuilder;
+
+    fn collections() -> Collections {
+        ModelBuilder::new()
+            .network("n1")
+            .line("l1")
+            .route("r1")
+            .vj("v1")
+            .build()
+            .into_collections()
+    }
+
+    #[test]
+    fn add_prefix_renames_ids_and_references() {
+        let mut c = collections();
+        add_prefix(&mut c, &PrefixConfiguration::new("p"));
+        assert!(c.networks.get("p:n1").is_some());
+        let line = c.lines.get("p:l1").unwrap();
+        assert_eq!(line.network_id, "p:n1");
+        assert_eq!(c.routes.get("p:r1").unwrap().line_id, "p:l1");
+        assert_eq!(c.vehicle_journeys.get("p:v1").unwrap().route_id, "p:r1");
+    }
+
+    #[test]
+    fn remove_prefix_reverts_add_prefix() {
+        let config = PrefixConfiguration::new("p")
+            .separator("_")
+            .prefix(ObjectType::Line, "q")
+            .exclude(ObjectType::Route);
+        let mut c = collections();
+        add_prefix(&mut c, &config);
+        assert!(c.lines.get("q_l1").is_some());
+        assert!(c.routes.get("r1").is_some());
+        remove_prefix(&mut c, &config);
+        let expected = collections();
+        assert_eq!(c.networks.into_vec(), expected.networks.into_vec());
+        assert_eq!(c.lines.into_vec(), expected.lines.into_vec());
+        assert_eq!(c.routes.into_vec(), expected.routes.into_vec());
+        assert_eq!(
+            c.vehicle_journeys.into_vec(),
+            expected.vehicle_journeys.into_vec()
+        );
+    }
+}
@@ -0,0 +1,43 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+// Number of records between two calls to `records_processed`.
+pub const BATCH_SIZE: usize = 10_000;
+
+// Files may be read or written from several threads at once, so the
+// callbacks can be called concurrently.
+pub trait ProgressObserver: Sync {
+    fn file_started(&self, _file: &str) {}
+    fn records_processed(&self, _file: &str, _count: usize) {}
+    fn file_finished(&self, _file: &str) {}
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+}
+
+pub struct NoProgress;
+
+impl ProgressObserver for NoProgress {}
+
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl ProgressObserver for CancellationToken {
+    fn is_cancelled(&self) -> bool {
+        CancellationToken::is_cancelled(self)
+    }
+}
@@ -0,0 +1,98 @@
+// pyo3 0.22 macro expansion trips this lint on every PyResult method
+#![allow(clippy::useless_conversion)]
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use serde_json;
+
+use error::Error;
+use prefix::{self, PrefixConfiguration, PrefixedType};
+use progress::NoProgress;
+use sanitize::SanitizeConfig;
+use {ntfs, stats, PtObjects};
+
+impl From<Error> for PyErr {
+    fn from(error: Error) -> PyErr {
+        PyValueError::new_err(error.to_string())
+    }
+}
+
+#[pyclass(name = "Model")]
+pub struct PyModel {
+    inner: PtObjects,
+}
+
+#[pymethods]
+impl PyModel {
+    fn write_ntfs(&self, path: &str) -> PyResult<()> {
+        Ok(ntfs::write_with_progress(path, &self.inner, &NoProgress)?)
+    }
+
+    fn sanitize(&mut self) -> PyResult<String> {
+        let mut report = None;
+        self.inner
+            .update(|c| report = Some(c.sanitize(&SanitizeConfig::default())));
+        let report = report.unwrap()?;
+        Ok(serde_json::to_string(&report).unwrap())
+    }
+
+    #[pyo3(signature = (prefix, separator = ":", excluded_types = vec![]))]
+    fn add_prefix(
+        &mut self,
+        prefix: &str,
+        separator: &str,
+        excluded_types: Vec<String>,
+    ) -> PyResult<()> {
+        let mut config = PrefixConfiguration::new(prefix).separator(separator);
+        for object_type in excluded_types {
+            config = config.exclude(parse_prefixed_type(&object_type)?);
+        }
+        self.inner.update(|c| prefix::add_prefix(c, &config));
+        Ok(())
+    }
+
+    fn stats(&self) -> String {
+        serde_json::to_string(&stats::compute(&self.inner)).unwrap()
+    }
+
+    fn to_json(&self) -> String {
+        serde_json::to_string(&self.inner).unwrap()
+    }
+
+    fn line_ids(&self) -> Vec<String> {
+        self.inner.lines.iter().map(|(_, l)| l.id.clone()).collect()
+    }
+}
+
+fn parse_prefixed_type(object_type: &str) -> PyResult<PrefixedType> {
+    match object_type {
+        "network" => Ok(PrefixedType::Network),
+        "line" => Ok(PrefixedType::Line),
+        "route" => Ok(PrefixedType::Route),
+        "trip" => Ok(PrefixedType::VehicleJourney),
+        "booking_rule" => Ok(PrefixedType::BookingRule),
+        "occupancy" => Ok(PrefixedType::Occupancy),
+        "vehicle_type" => Ok(PrefixedType::VehicleType),
+        "on_demand_zone" => Ok(PrefixedType::OnDemandZone),
+        "ticket" => Ok(PrefixedType::Ticket),
+        "ticket_use" => Ok(PrefixedType::TicketUse),
+        _ => Err(PyValueError::new_err(format!(
+            "unknown object type {}",
+            object_type
+        ))),
+    }
+}
+
+#[pyfunction]
+fn read_ntfs(path: &str) -> PyResult<PyModel> {
+    Ok(PyModel {
+        inner: ntfs::read_with_progress(path, &NoProgress)?,
+    })
+}
+
+#[pymodule]
+fn navitia_model(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyModel>()?;
+    m.add_function(wrap_pyfunction!(self::read_ntfs, m)?)?;
+    Ok(())
+}
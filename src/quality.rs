@@ -0,0 +1,298 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path;
+use csv;
+use serde_json;
+
+use collection::{Collection, Id};
+use error::{csv_error, io_error, json_error, Result};
+use objects::is_valid_color;
+use {ntfs, Collections};
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    pub severity: Severity,
+    pub check: &'static str,
+    pub object_type: &'static str,
+    pub object_id: String,
+    pub message: String,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct Report {
+    pub issues: Vec<Issue>,
+}
+
+impl Report {
+    pub fn count(&self, severity: Severity) -> usize {
+        self.issues
+            .iter()
+            .filter(|i| i.severity == severity)
+            .count()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.count(Severity::Error) > 0
+    }
+
+    fn push(
+        &mut self,
+        severity: Severity,
+        check: &'static str,
+        object_type: &'static str,
+        object_id: &str,
+        message: String,
+    ) {
+        self.issues.push(Issue {
+            severity,
+            check,
+            object_type,
+            object_id: object_id.to_string(),
+            message,
+        });
+    }
+}
+
+fn check_unused<T: Id<T>>(
+    report: &mut Report,
+    check: &'static str,
+    object_type: &'static str,
+    collection: &Collection<T>,
+    used: &HashSet<&str>,
+    message: &str,
+) {
+    for (_, obj) in collection.iter() {
+        if !used.contains(obj.id()) {
+            report.push(
+                Severity::Warning,
+                check,
+                object_type,
+                obj.id(),
+                message.to_string(),
+            );
+        }
+    }
+}
+
+fn is_all_caps(name: &str) -> bool {
+    name.chars().any(|c| c.is_alphabetic()) && !name.chars().any(|c| c.is_lowercase())
+}
+
+// Shares the checks of the NTFS reader so that both always agree.
+fn check_references(c: &Collections, report: &mut Report) {
+    for dangling in ntfs::check_references(c) {
+        report.push(
+            Severity::Error,
+            "dangling_reference",
+            dangling.object_type,
+            &dangling.id,
+            dangling.reference,
+        );
+    }
+}
+
+fn check_unused_objects(c: &Collections, report: &mut Report) {
+    let used = c
+        .vehicle_journeys
+        .iter()
+        .map(|(_, vj)| vj.route_id.as_str())
+        .collect();
+    check_unused(
+        report,
+        "route_without_trips",
+        "route",
+        &c.routes,
+        &used,
+        "route has no trip",
+    );
+    let used = c.routes.iter().map(|(_, r)| r.line_id.as_str()).collect();
+    check_unused(
+        report,
+        "line_without_routes",
+        "line",
+        &c.lines,
+        &used,
+        "line has no route",
+    );
+    let used = c.lines.iter().map(|(_, l)| l.network_id.as_str()).collect();
+    check_unused(
+        report,
+        "unused_network",
+        "network",
+        &c.networks,
+        &used,
+        "network has no line",
+    );
+    let used = c
+        .lines
+        .iter()
+        .map(|(_, l)| l.commercial_mode_id.as_str())
+        .collect();
+    check_unused(
+        report,
+        "unused_commercial_mode",
+        "commercial_mode",
+        &c.commercial_modes,
+        &used,
+        "commercial mode has no line",
+    );
+    let used = c
+        .vehicle_journeys
+        .iter()
+        .map(|(_, vj)| vj.physical_mode_id.as_str())
+        .collect();
+    check_unused(
+        report,
+        "unused_physical_mode",
+        "physical_mode",
+        &c.physical_modes,
+        &used,
+        "physical mode has no trip",
+    );
+    let used = c
+        .lines
+        .iter()
+        .filter_map(|(_, l)| l.booking_rule_id.as_deref())
+        .chain(
+            c.vehicle_journeys
+                .iter()
+                .filter_map(|(_, vj)| vj.booking_rule_id.as_deref()),
+        )
+        .chain(
+            c.on_demand_zones
+                .iter()
+                .filter_map(|(_, z)| z.booking_rule_id.as_deref()),
+        )
+        .collect();
+    check_unused(
+        report,
+        "unused_booking_rule",
+        "booking_rule",
+        &c.booking_rules,
+        &used,
+        "booking rule is not used",
+    );
+    let used = c
+        .vehicle_journeys
+        .iter()
+        .filter_map(|(_, vj)| vj.vehicle_type_id.as_deref())
+        .collect();
+    check_unused(
+        report,
+        "unused_vehicle_type",
+        "vehicle_type",
+        &c.vehicle_types,
+        &used,
+        "vehicle type has no trip",
+    );
+    let used = c
+        .lines
+        .iter()
+        .filter_map(|(_, l)| l.on_demand_zone_id.as_deref())
+        .chain(
+            c.vehicle_journeys
+                .iter()
+                .filter_map(|(_, vj)| vj.on_demand_zone_id.as_deref()),
+        )
+        .collect();
+    check_unused(
+        report,
+        "unused_on_demand_zone",
+        "on_demand_zone",
+        &c.on_demand_zones,
+        &used,
+        "on demand zone has no line or trip",
+    );
+}
+
+fn check_networks(c: &Collections, report: &mut Report) {
+    for (_, network) in c.networks.iter() {
+        if network.timezone.trim().is_empty() {
+            report.push(
+                Severity::Error,
+                "missing_timezone",
+                "network",
+                &network.id,
+                "network has no timezone".to_string(),
+            );
+        }
+    }
+}
+
+fn check_lines(c: &Collections, report: &mut Report) {
+    for (_, line) in c.lines.iter() {
+        if line.name.trim().is_empty() {
+            report.push(
+                Severity::Warning,
+                "missing_name",
+                "line",
+                &line.id,
+                "line has no name".to_string(),
+            );
+        } else if is_all_caps(&line.name) {
+            report.push(
+                Severity::Info,
+                "all_caps_name",
+                "line",
+                &line.id,
+                format!("line name {} is in capital letters", line.name),
+            );
+        }
+        for &(field, color) in &[
+            ("line_color", &line.color),
+            ("line_text_color", &line.text_color),
+        ] {
+            match *color {
+                None => report.push(
+                    Severity::Info,
+                    "missing_color",
+                    "line",
+                    &line.id,
+                    format!("{} is missing", field),
+                ),
+                Some(ref color) if !is_valid_color(color) => report.push(
+                    Severity::Warning,
+                    "invalid_color",
+                    "line",
+                    &line.id,
+                    format!("{} {} is not a valid color", field, color),
+                ),
+                Some(_) => {}
+            }
+        }
+    }
+}
+
+// Works on Collections rather than PtObjects so that a model with dangling
+// references can be checked without building the relations.
+pub fn compute(collections: &Collections) -> Report {
+    let mut report = Report::default();
+    check_references(collections, &mut report);
+    check_unused_objects(collections, &mut report);
+    check_networks(collections, &mut report);
+    check_lines(collections, &mut report);
+    report
+}
+
+pub fn write_csv<P: AsRef<path::Path>>(path: P, report: &Report) -> Result<()> {
+    let path = path.as_ref();
+    let mut wtr = csv::Writer::from_path(path).map_err(|e| csv_error(path, e))?;
+    for issue in &report.issues {
+        wtr.serialize(issue).map_err(|e| csv_error(path, e))?;
+    }
+    wtr.flush().map_err(|e| io_error(path, e))
+}
+
+pub fn write_json<P: AsRef<path::Path>>(path: P, report: &Report) -> Result<()> {
+    let path = path.as_ref();
+    let file = fs::File::create(path).map_err(|e| io_error(path, e))?;
+    serde_json::to_writer_pretty(file, report).map_err(|e| json_error(path, e))
+}
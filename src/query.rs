@@ -0,0 +1,84 @@
+use collection::{Collection, Id};
+use error::Result;
+use objects::*;
+use relations::{GetCorresponding, IdxSet};
+use {not_found, PtObjects};
+
+impl PtObjects {
+    // Objects are returned in the order of their collection.
+    fn corresponding<'a, T, U>(
+        &'a self,
+        object_type: &'static str,
+        from: &Collection<T>,
+        id: &str,
+        to: &'a Collection<U>,
+    ) -> Result<Vec<&'a U>>
+    where
+        T: Id<T>,
+        U: Id<U>,
+        IdxSet<T>: GetCorresponding<U>,
+    {
+        let idx = from.get_idx(id).ok_or_else(|| not_found(object_type, id))?;
+        let from: IdxSet<T> = Some(idx).into_iter().collect();
+        let to_idxs: IdxSet<U> = from.get_corresponding(self);
+        Ok(to
+            .iter()
+            .filter(|&(idx, _)| to_idxs.contains(&idx))
+            .map(|(_, obj)| obj)
+            .collect())
+    }
+
+    pub fn lines_of_network(&self, id: &str) -> Result<Vec<&Line>> {
+        self.corresponding("network", &self.networks, id, &self.lines)
+    }
+
+    pub fn lines_of_commercial_mode(&self, id: &str) -> Result<Vec<&Line>> {
+        self.corresponding("commercial_mode", &self.commercial_modes, id, &self.lines)
+    }
+
+    pub fn routes_of_line(&self, id: &str) -> Result<Vec<&Route>> {
+        self.corresponding("line", &self.lines, id, &self.routes)
+    }
+
+    pub fn vehicle_journeys_of_line(&self, id: &str) -> Result<Vec<&VehicleJourney>> {
+        self.corresponding("line", &self.lines, id, &self.vehicle_journeys)
+    }
+
+    pub fn vehicle_journeys_of_route(&self, id: &str) -> Result<Vec<&VehicleJourney>> {
+        self.corresponding("route", &self.routes, id, &self.vehicle_journeys)
+    }
+
+    pub fn physical_modes_of_line(&self, id: &str) -> Result<Vec<&PhysicalMode>> {
+        self.corresponding("line", &self.lines, id, &self.physical_modes)
+    }
+
+    pub fn occupancies_of_vehicle_journey(&self, id: &str) -> Result<Vec<&Occupancy>> {
+        self.corresponding(
+            "vehicle_journey",
+            &self.vehicle_journeys,
+            id,
+            &self.occupancies,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use builder::ModelBuilder;
+
+    #[test]
+    fn dangling_references_are_not_linked() {
+        let pt_objects = ModelBuilder::new()
+            .network("n1")
+            .line("l1")
+            .vj("v1")
+            .line_with("l2", |l| l.network_id = "unknown".to_string())
+            .vj_with("v2", |vj| vj.route_id = "unknown".to_string())
+            .build();
+        let lines = pt_objects.lines_of_network("n1").unwrap();
+        assert_eq!(lines.iter().map(|l| &l.id).collect::<Vec<_>>(), ["l1"]);
+        assert!(pt_objects.vehicle_journeys_of_line("l2").unwrap().is_empty());
+        let vjs = pt_objects.vehicle_journeys_of_line("l1").unwrap();
+        assert_eq!(vjs.iter().map(|vj| &vj.id).collect::<Vec<_>>(), ["v1"]);
+    }
+}
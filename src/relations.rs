@@ -1,49 +1,101 @@
 use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
 use collection::{Collection, Id, Idx};
+use Collections;
 
 pub type IdxSet<T> = HashSet<Idx<T>>;
 
 pub trait GetCorresponding<T: Sized> {
-    fn get_corresponding(&self, &::PtObjects) -> IdxSet<T>;
+    fn get_corresponding(&self, _: &::PtObjects) -> IdxSet<T>;
 }
 
-pub struct OneToMany<T, U> {
+struct Relation<T, U> {
     one_to_many: HashMap<Idx<T>, IdxSet<U>>,
     many_to_one: HashMap<Idx<U>, Idx<T>>,
 }
 
-impl<T, U> OneToMany<T, U>
+impl<T, U> Relation<T, U>
 where
     T: Id<T>,
     U: Id<U> + Id<T>,
 {
-    pub fn new(one: &Collection<T>, many: &Collection<U>) -> Self {
+    // Dangling references are left out of the relation: they are reported by
+    // `ntfs::check_references`, not when querying.
+    fn new(one: &Collection<T>, many: &Collection<U>) -> Self {
         let mut one_to_many = HashMap::default();
         let mut many_to_one = HashMap::default();
         for (many_idx, obj) in many.iter() {
-            let one_idx = one.get_idx(<U as Id<T>>::id(obj)).unwrap();
+            let one_idx = match one.get_idx(<U as Id<T>>::id(obj)) {
+                Some(one_idx) => one_idx,
+                None => continue,
+            };
             many_to_one.insert(many_idx, one_idx);
             one_to_many
                 .entry(one_idx)
                 .or_insert_with(HashSet::default)
                 .insert(many_idx);
         }
+        Relation {
+            one_to_many,
+            many_to_one,
+        }
+    }
+}
+
+pub type Select<T, U> = fn(&Collections) -> (&Collection<T>, &Collection<U>);
+
+pub struct OneToMany<T, U> {
+    select: Select<T, U>,
+    relation: OnceLock<Relation<T, U>>,
+}
+
+impl<T, U> OneToMany<T, U>
+where
+    T: Id<T>,
+    U: Id<U> + Id<T>,
+{
+    pub fn new(select: Select<T, U>) -> Self {
         OneToMany {
-            one_to_many: one_to_many,
-            many_to_one: many_to_one,
+            select,
+            relation: OnceLock::new(),
         }
     }
 
-    pub fn get_corresponding_forward(&self, from: &IdxSet<T>) -> IdxSet<U> {
+    fn relation(&self, collections: &Collections) -> &Relation<T, U> {
+        self.relation.get_or_init(|| {
+            let (one, many) = (self.select)(collections);
+            Relation::new(one, many)
+        })
+    }
+
+    pub fn build(&self, collections: &Collections) {
+        self.relation(collections);
+    }
+
+    pub fn invalidate(&mut self) {
+        self.relation = OnceLock::new();
+    }
+
+    pub fn get_corresponding_forward(
+        &self,
+        collections: &Collections,
+        from: &IdxSet<T>,
+    ) -> IdxSet<U> {
+        let relation = self.relation(collections);
         from.iter()
-            .filter_map(|from_idx| self.one_to_many.get(from_idx))
+            .filter_map(|from_idx| relation.one_to_many.get(from_idx))
             .flat_map(|indices| indices.iter().cloned())
             .collect()
     }
 
-    pub fn get_corresponding_backward(&self, from: &IdxSet<U>) -> IdxSet<T> {
+    pub fn get_corresponding_backward(
+        &self,
+        collections: &Collections,
+        from: &IdxSet<U>,
+    ) -> IdxSet<T> {
+        let relation = self.relation(collections);
         from.iter()
-            .filter_map(|from_idx| self.many_to_one.get(from_idx))
+            .filter_map(|from_idx| relation.many_to_one.get(from_idx))
             .cloned()
             .collect()
     }
@@ -0,0 +1,611 @@
+use std::collections::HashSet;
+use std::mem;
+
+use collection::{Collection, Id};
+use error::{Error, Result};
+use objects::{is_valid_color, readable_text_color, ObjectCode, TicketPrice, TicketUsePerimeter};
+use Collections;
+
+#[derive(Debug, Clone)]
+pub struct SanitizeConfig {
+    pub fail_on_dangling_references: bool,
+    pub remove_unused_networks: bool,
+    pub remove_unused_commercial_modes: bool,
+    pub remove_unused_physical_modes: bool,
+    pub remove_unused_booking_rules: bool,
+    pub remove_unused_vehicle_types: bool,
+    pub remove_unused_on_demand_zones: bool,
+    pub remove_lines_without_routes: bool,
+    pub remove_routes_without_trips: bool,
+    pub fix_line_colors: bool,
+}
+
+impl Default for SanitizeConfig {
+    fn default() -> Self {
+        SanitizeConfig {
+            fail_on_dangling_references: false,
+            remove_unused_networks: true,
+            remove_unused_commercial_modes: true,
+            remove_unused_physical_modes: true,
+            remove_unused_booking_rules: true,
+            remove_unused_vehicle_types: true,
+            remove_unused_on_demand_zones: true,
+            remove_lines_without_routes: true,
+            remove_routes_without_trips: true,
+            fix_line_colors: false,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SanitizeEntry {
+    pub object_type: &'static str,
+    pub id: String,
+    pub reason: String,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct SanitizeReport {
+    pub removed: Vec<SanitizeEntry>,
+    pub repaired: Vec<SanitizeEntry>,
+}
+
+pub(crate) fn dangling<T: Id<T>>(collection: &Collection<T>, field: &str, id: &str) -> Option<String> {
+    if collection.get(id).is_some() {
+        None
+    } else {
+        Some(format!("{} {} not found", field, id))
+    }
+}
+
+fn invalid_reference(object_type: &'static str, id: &str, reference: String) -> Error {
+    Error::InvalidReference {
+        object_type,
+        id: id.to_string(),
+        reference,
+        file: None,
+        line: None,
+    }
+}
+
+fn remove_dangling<T, F>(
+    object_type: &'static str,
+    collection: &mut Collection<T>,
+    config: &SanitizeConfig,
+    report: &mut SanitizeReport,
+    dangling: F,
+) -> Result<()>
+where
+    T: Id<T>,
+    F: Fn(&T) -> Option<String>,
+{
+    let mut removed = HashSet::new();
+    for (_, obj) in collection.iter() {
+        if let Some(reason) = dangling(obj) {
+            if config.fail_on_dangling_references {
+                return Err(invalid_reference(object_type, obj.id(), reason));
+            }
+            removed.insert(obj.id().to_string());
+            report.removed.push(SanitizeEntry {
+                object_type,
+                id: obj.id().to_string(),
+                reason,
+            });
+        }
+    }
+    if !removed.is_empty() {
+        collection.retain(|obj| !removed.contains(obj.id()));
+    }
+    Ok(())
+}
+
+fn clear_dangling_reference<'a, I>(
+    object_type: &'static str,
+    field: &str,
+    objects: I,
+    ids: &HashSet<String>,
+    config: &SanitizeConfig,
+    report: &mut SanitizeReport,
+) -> Result<()>
+where
+    I: Iterator<Item = (&'a str, &'a mut Option<String>)>,
+{
+    for (id, reference) in objects {
+        let reason = match *reference {
+            Some(ref reference) if !ids.contains(reference) => {
+                format!("{} {} not found", field, reference)
+            }
+            _ => continue,
+        };
+        if config.fail_on_dangling_references {
+            return Err(invalid_reference(object_type, id, reason));
+        }
+        *reference = None;
+        report.repaired.push(SanitizeEntry {
+            object_type,
+            id: id.to_string(),
+            reason,
+        });
+    }
+    Ok(())
+}
+
+fn remove_unused<T: Id<T>>(
+    object_type: &'static str,
+    collection: &mut Collection<T>,
+    used: &HashSet<String>,
+    reason: &str,
+    report: &mut SanitizeReport,
+) {
+    for (_, obj) in collection.iter() {
+        if !used.contains(obj.id()) {
+            report.removed.push(SanitizeEntry {
+                object_type,
+                id: obj.id().to_string(),
+                reason: reason.to_string(),
+            });
+        }
+    }
+    collection.retain(|obj| used.contains(obj.id()));
+}
+
+fn clear_invalid_color(
+    line_id: &str,
+    field: &str,
+    color: &mut Option<String>,
+    report: &mut SanitizeReport,
+) {
+    let reason = match *color {
+        Some(ref c) if !is_valid_color(c) => format!("{} {} is not a valid color", field, c),
+        _ => return,
+    };
+    *color = None;
+    report.repaired.push(SanitizeEntry {
+        object_type: "line",
+        id: line_id.to_string(),
+        reason,
+    });
+}
+
+impl Collections {
+    pub fn sanitize(
+        &mut self,
+        config: &SanitizeConfig,
+    ) -> Result<SanitizeReport> {
+        let mut report = SanitizeReport::default();
+        self.remove_dangling_references(config, &mut report)?;
+        if config.fail_on_dangling_references {
+            self.check_dangling_codes_and_fares()?;
+        }
+        self.remove_unused_objects(config, &mut report);
+        self.remove_dangling_object_codes(&mut report);
+        self.remove_dangling_fares(&mut report);
+        if config.fix_line_colors {
+            self.fix_line_colors_into(&mut report);
+        }
+        Ok(report)
+    }
+
+    // Invalid colors are cleared and a missing text color is computed from
+    // the line color.
+    pub fn fix_line_colors(&mut self) -> SanitizeReport {
+        let mut report = SanitizeReport::default();
+        self.fix_line_colors_into(&mut report);
+        report
+    }
+
+    fn fix_line_colors_into(&mut self, report: &mut SanitizeReport) {
+        for line in self.lines.iter_mut() {
+            clear_invalid_color(&line.id, "line_color", &mut line.color, report);
+            clear_invalid_color(&line.id, "line_text_color", &mut line.text_color, report);
+            if line.text_color.is_some() {
+                continue;
+            }
+            if let Some(text_color) = line.color.as_ref().and_then(|c| readable_text_color(c)) {
+                line.text_color = Some(text_color.to_string());
+                report.repaired.push(SanitizeEntry {
+                    object_type: "line",
+                    id: line.id.clone(),
+                    reason: format!("line_text_color set to {}", text_color),
+                });
+            }
+        }
+    }
+
+    fn remove_dangling_references(
+        &mut self,
+        config: &SanitizeConfig,
+        report: &mut SanitizeReport,
+    ) -> Result<()> {
+        let Collections {
+            ref networks,
+            ref commercial_modes,
+            ref physical_modes,
+            ref booking_rules,
+            ref vehicle_types,
+            ref mut on_demand_zones,
+            ref mut lines,
+            ref mut routes,
+            ref mut vehicle_journeys,
+            ref mut occupancies,
+            ref tickets,
+            ref mut ticket_uses,
+            ..
+        } = *self;
+        let booking_rule_ids = booking_rules
+            .iter()
+            .map(|(_, b)| b.id.clone())
+            .collect();
+        let vehicle_type_ids = vehicle_types
+            .iter()
+            .map(|(_, t)| t.id.clone())
+            .collect();
+        let on_demand_zone_ids = on_demand_zones
+            .iter()
+            .map(|(_, z)| z.id.clone())
+            .collect();
+
+        clear_dangling_reference(
+            "on_demand_zone",
+            "booking_rule_id",
+            on_demand_zones
+                .iter_mut()
+                .map(|z| (z.id.as_str(), &mut z.booking_rule_id)),
+            &booking_rule_ids,
+            config,
+            report,
+        )?;
+
+        remove_dangling("line", lines, config, report, |l| {
+            dangling(networks, "network_id", &l.network_id).or_else(|| {
+                dangling(commercial_modes, "commercial_mode_id", &l.commercial_mode_id)
+            })
+        })?;
+        clear_dangling_reference(
+            "line",
+            "booking_rule_id",
+            lines
+                .iter_mut()
+                .map(|l| (l.id.as_str(), &mut l.booking_rule_id)),
+            &booking_rule_ids,
+            config,
+            report,
+        )?;
+        clear_dangling_reference(
+            "line",
+            "on_demand_zone_id",
+            lines
+                .iter_mut()
+                .map(|l| (l.id.as_str(), &mut l.on_demand_zone_id)),
+            &on_demand_zone_ids,
+            config,
+            report,
+        )?;
+        remove_dangling("route", routes, config, report, |r| {
+            dangling(lines, "line_id", &r.line_id)
+        })?;
+        remove_dangling("vehicle_journey", vehicle_journeys, config, report, |vj| {
+            dangling(routes, "route_id", &vj.route_id)
+                .or_else(|| dangling(physical_modes, "physical_mode_id", &vj.physical_mode_id))
+        })?;
+        clear_dangling_reference(
+            "vehicle_journey",
+            "booking_rule_id",
+            vehicle_journeys
+                .iter_mut()
+                .map(|vj| (vj.id.as_str(), &mut vj.booking_rule_id)),
+            &booking_rule_ids,
+            config,
+            report,
+        )?;
+        clear_dangling_reference(
+            "vehicle_journey",
+            "vehicle_type_id",
+            vehicle_journeys
+                .iter_mut()
+                .map(|vj| (vj.id.as_str(), &mut vj.vehicle_type_id)),
+            &vehicle_type_ids,
+            config,
+            report,
+        )?;
+        clear_dangling_reference(
+            "vehicle_journey",
+            "on_demand_zone_id",
+            vehicle_journeys
+                .iter_mut()
+                .map(|vj| (vj.id.as_str(), &mut vj.on_demand_zone_id)),
+            &on_demand_zone_ids,
+            config,
+            report,
+        )?;
+        remove_dangling("occupancy", occupancies, config, report, |o| {
+            dangling(vehicle_journeys, "trip_id", &o.vehicle_journey_id)
+        })?;
+        remove_dangling("ticket_use", ticket_uses, config, report, |u| {
+            dangling(tickets, "ticket_id", &u.ticket_id)
+        })?;
+        Ok(())
+    }
+
+    fn remove_unused_objects(&mut self, config: &SanitizeConfig, report: &mut SanitizeReport) {
+        if config.remove_routes_without_trips {
+            let used = self.vehicle_journeys
+                .iter()
+                .map(|(_, vj)| vj.route_id.clone())
+                .collect();
+            remove_unused("route", &mut self.routes, &used, "no trip", report);
+        }
+        if config.remove_lines_without_routes {
+            let used = self.routes
+                .iter()
+                .map(|(_, r)| r.line_id.clone())
+                .collect();
+            remove_unused("line", &mut self.lines, &used, "no route", report);
+        }
+        if config.remove_unused_networks {
+            let used = self.lines
+                .iter()
+                .map(|(_, l)| l.network_id.clone())
+                .collect();
+            remove_unused("network", &mut self.networks, &used, "no line", report);
+        }
+        if config.remove_unused_commercial_modes {
+            let used = self.lines
+                .iter()
+                .map(|(_, l)| l.commercial_mode_id.clone())
+                .collect();
+            remove_unused(
+                "commercial_mode",
+                &mut self.commercial_modes,
+                &used,
+                "no line",
+                report,
+            );
+        }
+        if config.remove_unused_physical_modes {
+            let used = self.vehicle_journeys
+                .iter()
+                .map(|(_, vj)| vj.physical_mode_id.clone())
+                .collect();
+            remove_unused(
+                "physical_mode",
+                &mut self.physical_modes,
+                &used,
+                "no trip",
+                report,
+            );
+        }
+        if config.remove_unused_on_demand_zones {
+            let used = self.lines
+                .iter()
+                .filter_map(|(_, l)| l.on_demand_zone_id.clone())
+                .chain(
+                    self.vehicle_journeys
+                        .iter()
+                        .filter_map(|(_, vj)| vj.on_demand_zone_id.clone()),
+                )
+                .collect();
+            remove_unused(
+                "on_demand_zone",
+                &mut self.on_demand_zones,
+                &used,
+                "no line or trip",
+                report,
+            );
+        }
+        if config.remove_unused_booking_rules {
+            let used = self.lines
+                .iter()
+                .filter_map(|(_, l)| l.booking_rule_id.clone())
+                .chain(
+                    self.vehicle_journeys
+                        .iter()
+                        .filter_map(|(_, vj)| vj.booking_rule_id.clone()),
+                )
+                .chain(
+                    self.on_demand_zones
+                        .iter()
+                        .filter_map(|(_, z)| z.booking_rule_id.clone()),
+                )
+                .collect();
+            remove_unused(
+                "booking_rule",
+                &mut self.booking_rules,
+                &used,
+                "no line, trip or on demand zone",
+                report,
+            );
+        }
+        if config.remove_unused_vehicle_types {
+            let used = self.vehicle_journeys
+                .iter()
+                .filter_map(|(_, vj)| vj.vehicle_type_id.clone())
+                .collect();
+            remove_unused(
+                "vehicle_type",
+                &mut self.vehicle_types,
+                &used,
+                "no trip",
+                report,
+            );
+        }
+    }
+
+    fn dangling_object_code(&self, code: &ObjectCode) -> Option<String> {
+        if self.object_exists(code.object_type, &code.object_id) {
+            None
+        } else {
+            Some(format!(
+                "code {}:{} refers to a missing object",
+                code.object_system, code.object_code
+            ))
+        }
+    }
+
+    fn dangling_ticket_price(&self, price: &TicketPrice) -> Option<String> {
+        dangling(&self.tickets, "ticket_id", &price.ticket_id)
+    }
+
+    fn dangling_perimeter(&self, perimeter: &TicketUsePerimeter) -> Option<String> {
+        dangling(&self.ticket_uses, "ticket_use_id", &perimeter.ticket_use_id).or_else(|| {
+            if self.object_exists(perimeter.object_type, &perimeter.object_id) {
+                None
+            } else {
+                Some(format!("object_id {} not found", perimeter.object_id))
+            }
+        })
+    }
+
+    // Codes and fares are removed after the unused objects, which may leave
+    // them dangling on purpose; only the references of the input are checked
+    // here.
+    fn check_dangling_codes_and_fares(&self) -> Result<()> {
+        for code in &self.object_codes {
+            if let Some(reason) = self.dangling_object_code(code) {
+                return Err(invalid_reference("object_code", &code.object_id, reason));
+            }
+        }
+        for price in &self.ticket_prices {
+            if let Some(reason) = self.dangling_ticket_price(price) {
+                return Err(invalid_reference("ticket_price", &price.ticket_id, reason));
+            }
+        }
+        for perimeter in &self.ticket_use_perimeters {
+            if let Some(reason) = self.dangling_perimeter(perimeter) {
+                return Err(invalid_reference(
+                    "ticket_use_perimeter",
+                    &perimeter.ticket_use_id,
+                    reason,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn remove_dangling_object_codes(&mut self, report: &mut SanitizeReport) {
+        let mut object_codes = mem::take(&mut self.object_codes);
+        object_codes.retain(|code| match self.dangling_object_code(code) {
+            Some(reason) => {
+                report.removed.push(SanitizeEntry {
+                    object_type: "object_code",
+                    id: code.object_id.clone(),
+                    reason,
+                });
+                false
+            }
+            None => true,
+        });
+        self.object_codes = object_codes;
+    }
+
+    // Prices and perimeters have no id of their own, they are reported
+    // with the id of the ticket or ticket use they belong to.
+    fn remove_dangling_fares(&mut self, report: &mut SanitizeReport) {
+        let mut prices = mem::take(&mut self.ticket_prices);
+        prices.retain(|p| match self.dangling_ticket_price(p) {
+            Some(reason) => {
+                report.removed.push(SanitizeEntry {
+                    object_type: "ticket_price",
+                    id: p.ticket_id.clone(),
+                    reason,
+                });
+                false
+            }
+            None => true,
+        });
+        self.ticket_prices = prices;
+        let mut perimeters = mem::take(&mut self.ticket_use_perimeters);
+        perimeters.retain(|p| match self.dangling_perimeter(p) {
+            Some(reason) => {
+                report.removed.push(SanitizeEntry {
+                    object_type: "ticket_use_perimeter",
+                    id: p.ticket_use_id.clone(),
+                    reason,
+                });
+                false
+            }
+            None => true,
+        });
+        self.ticket_use_perimeters = perimeters;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builder::ModelBuilder;
+
+    fn removed(report: &SanitizeReport) -> Vec<(&str, &str)> {
+        report
+            .removed
+            .iter()
+            .map(|e| (e.object_type, e.id.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn removing_a_dangling_trip_cascades_to_its_network() {
+        let mut collections = ModelBuilder::new()
+            .network("n1")
+            .line("l1")
+            .route("r1")
+            .vj("v1")
+            .network("n2")
+            .line("l2")
+            .route("r2")
+            .vj_with("v2", |vj| vj.physical_mode_id = "unknown".to_string())
+            .build()
+            .into_collections();
+        let report = collections.sanitize(&SanitizeConfig::default()).unwrap();
+        assert_eq!(
+            removed(&report),
+            vec![
+                ("vehicle_journey", "v2"),
+                ("route", "r2"),
+                ("line", "l2"),
+                ("network", "n2"),
+            ]
+        );
+        assert_eq!(report.removed[0].reason, "physical_mode_id unknown not found");
+        assert!(collections.networks.get("n1").is_some());
+        assert!(collections.lines.get("l1").is_some());
+        assert!(collections.routes.get("r1").is_some());
+        assert!(collections.vehicle_journeys.get("v1").is_some());
+    }
+
+    #[test]
+    fn disabled_steps_stop_the_cascade() {
+        let mut collections = ModelBuilder::new()
+            .line("l1")
+            .route("r1")
+            .build()
+            .into_collections();
+        let config = SanitizeConfig {
+            remove_lines_without_routes: false,
+            ..SanitizeConfig::default()
+        };
+        let report = collections.sanitize(&config).unwrap();
+        assert_eq!(removed(&report), vec![("route", "r1")]);
+        assert!(collections.lines.get("l1").is_some());
+    }
+
+    #[test]
+    fn dangling_references_fail_when_configured() {
+        let mut collections = ModelBuilder::new()
+            .line_with("l1", |l| l.network_id = "unknown".to_string())
+            .vj("v1")
+            .build()
+            .into_collections();
+        let config = SanitizeConfig {
+            fail_on_dangling_references: true,
+            ..SanitizeConfig::default()
+        };
+        match collections.sanitize(&config) {
+            Err(Error::InvalidReference { object_type, id, .. }) => {
+                assert_eq!(object_type, "line");
+                assert_eq!(id, "l1");
+            }
+            other => panic!("unexpected result {:?}", other.map(|r| r.removed)),
+        }
+    }
+}
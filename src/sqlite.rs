@@ -0,0 +1,271 @@
+use std::path;
+use rusqlite::{Connection, Result};
+
+use objects::{ObjectType, Propulsion};
+use PtObjects;
+
+const SCHEMA: &str = "
+CREATE TABLE networks (
+    network_id TEXT PRIMARY KEY,
+    network_name TEXT NOT NULL,
+    network_timezone TEXT NOT NULL
+);
+CREATE TABLE commercial_modes (
+    commercial_mode_id TEXT PRIMARY KEY,
+    commercial_mode_name TEXT NOT NULL
+);
+CREATE TABLE physical_modes (
+    physical_mode_id TEXT PRIMARY KEY,
+    physical_mode_name TEXT NOT NULL
+);
+CREATE TABLE booking_rules (
+    booking_rule_id TEXT PRIMARY KEY,
+    booking_rule_name TEXT NOT NULL,
+    phone_number TEXT,
+    booking_url TEXT,
+    prior_notice_duration_min INTEGER,
+    message TEXT
+);
+CREATE TABLE on_demand_zones (
+    on_demand_zone_id TEXT PRIMARY KEY,
+    on_demand_zone_name TEXT NOT NULL,
+    on_demand_zone_shape TEXT NOT NULL,
+    pickup_type INTEGER,
+    drop_off_type INTEGER,
+    booking_rule_id TEXT REFERENCES booking_rules(booking_rule_id)
+);
+CREATE TABLE lines (
+    line_id TEXT PRIMARY KEY,
+    line_name TEXT NOT NULL,
+    network_id TEXT NOT NULL REFERENCES networks(network_id),
+    commercial_mode_id TEXT NOT NULL REFERENCES commercial_modes(commercial_mode_id),
+    booking_rule_id TEXT REFERENCES booking_rules(booking_rule_id),
+    line_color TEXT,
+    line_text_color TEXT,
+    on_demand_zone_id TEXT REFERENCES on_demand_zones(on_demand_zone_id)
+);
+CREATE INDEX lines_network_id ON lines(network_id);
+CREATE INDEX lines_commercial_mode_id ON lines(commercial_mode_id);
+CREATE TABLE routes (
+    route_id TEXT PRIMARY KEY,
+    route_name TEXT NOT NULL,
+    line_id TEXT NOT NULL REFERENCES lines(line_id)
+);
+CREATE INDEX routes_line_id ON routes(line_id);
+CREATE TABLE vehicle_types (
+    vehicle_type_id TEXT PRIMARY KEY,
+    vehicle_type_name TEXT NOT NULL,
+    seated_capacity INTEGER,
+    standing_capacity INTEGER,
+    floor_height REAL,
+    bike_spaces INTEGER,
+    propulsion TEXT
+);
+CREATE TABLE trips (
+    trip_id TEXT PRIMARY KEY,
+    route_id TEXT NOT NULL REFERENCES routes(route_id),
+    physical_mode_id TEXT NOT NULL REFERENCES physical_modes(physical_mode_id),
+    booking_rule_id TEXT REFERENCES booking_rules(booking_rule_id),
+    wheelchair_accessible INTEGER,
+    bikes_allowed INTEGER,
+    stroller_allowed INTEGER,
+    luggage_allowed INTEGER,
+    vehicle_type_id TEXT REFERENCES vehicle_types(vehicle_type_id),
+    on_demand_zone_id TEXT REFERENCES on_demand_zones(on_demand_zone_id)
+);
+CREATE INDEX trips_route_id ON trips(route_id);
+CREATE INDEX trips_physical_mode_id ON trips(physical_mode_id);
+CREATE TABLE occupancies (
+    occupancy_id TEXT PRIMARY KEY,
+    trip_id TEXT NOT NULL REFERENCES trips(trip_id),
+    stop_sequence INTEGER,
+    occupancy INTEGER NOT NULL
+);
+CREATE INDEX occupancies_trip_id ON occupancies(trip_id);
+CREATE TABLE object_codes (
+    object_type TEXT NOT NULL,
+    object_id TEXT NOT NULL,
+    object_system TEXT NOT NULL,
+    object_code TEXT NOT NULL
+);
+CREATE INDEX object_codes_object ON object_codes(object_type, object_id);
+CREATE TABLE tickets (
+    ticket_id TEXT PRIMARY KEY,
+    ticket_name TEXT NOT NULL,
+    ticket_comment TEXT
+);
+CREATE TABLE ticket_prices (
+    ticket_id TEXT NOT NULL REFERENCES tickets(ticket_id),
+    ticket_price REAL NOT NULL,
+    ticket_currency TEXT NOT NULL,
+    ticket_validity_start TEXT NOT NULL,
+    ticket_validity_end TEXT NOT NULL
+);
+CREATE INDEX ticket_prices_ticket_id ON ticket_prices(ticket_id);
+CREATE TABLE ticket_uses (
+    ticket_use_id TEXT PRIMARY KEY,
+    ticket_id TEXT NOT NULL REFERENCES tickets(ticket_id),
+    max_transfers INTEGER,
+    boarding_time_limit INTEGER,
+    alighting_time_limit INTEGER
+);
+CREATE INDEX ticket_uses_ticket_id ON ticket_uses(ticket_id);
+CREATE TABLE ticket_use_perimeters (
+    ticket_use_id TEXT NOT NULL REFERENCES ticket_uses(ticket_use_id),
+    object_type TEXT NOT NULL,
+    object_id TEXT NOT NULL,
+    perimeter_action INTEGER NOT NULL
+);
+CREATE INDEX ticket_use_perimeters_ticket_use_id ON ticket_use_perimeters(ticket_use_id);
+";
+
+fn object_type_name(object_type: ObjectType) -> &'static str {
+    match object_type {
+        ObjectType::Network => "network",
+        ObjectType::Line => "line",
+        ObjectType::Route => "route",
+        ObjectType::VehicleJourney => "trip",
+    }
+}
+
+fn propulsion_name(propulsion: Propulsion) -> &'static str {
+    match propulsion {
+        Propulsion::Diesel => "diesel",
+        Propulsion::Electric => "electric",
+        Propulsion::Hybrid => "hybrid",
+        Propulsion::Hydrogen => "hydrogen",
+        Propulsion::NaturalGas => "natural_gas",
+        Propulsion::Other => "other",
+    }
+}
+
+pub fn write<P: AsRef<path::Path>>(path: P, pt_objects: &PtObjects) -> Result<()> {
+    let mut conn = Connection::open(path)?;
+    conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+    let tx = conn.transaction()?;
+    tx.execute_batch(SCHEMA)?;
+    {
+        let mut stmt = tx.prepare("INSERT INTO networks VALUES (?1, ?2, ?3)")?;
+        for (_, n) in pt_objects.networks.iter() {
+            stmt.execute((&n.id, &n.name, &n.timezone))?;
+        }
+        let mut stmt = tx.prepare("INSERT INTO commercial_modes VALUES (?1, ?2)")?;
+        for (_, m) in pt_objects.commercial_modes.iter() {
+            stmt.execute((&m.id, &m.name))?;
+        }
+        let mut stmt = tx.prepare("INSERT INTO physical_modes VALUES (?1, ?2)")?;
+        for (_, m) in pt_objects.physical_modes.iter() {
+            stmt.execute((&m.id, &m.name))?;
+        }
+        let mut stmt = tx.prepare("INSERT INTO booking_rules VALUES (?1, ?2, ?3, ?4, ?5, ?6)")?;
+        for (_, b) in pt_objects.booking_rules.iter() {
+            stmt.execute((&b.id, &b.name, &b.phone, &b.url, &b.deadline, &b.conditions))?;
+        }
+        let mut stmt =
+            tx.prepare("INSERT INTO on_demand_zones VALUES (?1, ?2, ?3, ?4, ?5, ?6)")?;
+        for (_, z) in pt_objects.on_demand_zones.iter() {
+            stmt.execute((
+                &z.id,
+                &z.name,
+                &z.shape,
+                z.pickup_type.map(|t| t as u8),
+                z.drop_off_type.map(|t| t as u8),
+                &z.booking_rule_id,
+            ))?;
+        }
+        let mut stmt =
+            tx.prepare("INSERT INTO lines VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)")?;
+        for (_, l) in pt_objects.lines.iter() {
+            stmt.execute((
+                &l.id,
+                &l.name,
+                &l.network_id,
+                &l.commercial_mode_id,
+                &l.booking_rule_id,
+                &l.color,
+                &l.text_color,
+                &l.on_demand_zone_id,
+            ))?;
+        }
+        let mut stmt = tx.prepare("INSERT INTO routes VALUES (?1, ?2, ?3)")?;
+        for (_, r) in pt_objects.routes.iter() {
+            stmt.execute((&r.id, &r.name, &r.line_id))?;
+        }
+        let mut stmt =
+            tx.prepare("INSERT INTO vehicle_types VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)")?;
+        for (_, t) in pt_objects.vehicle_types.iter() {
+            stmt.execute((
+                &t.id,
+                &t.name,
+                &t.seated_capacity,
+                &t.standing_capacity,
+                &t.floor_height,
+                &t.bike_spaces,
+                t.propulsion.map(propulsion_name),
+            ))?;
+        }
+        let mut stmt =
+            tx.prepare("INSERT INTO trips VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)")?;
+        for (_, vj) in pt_objects.vehicle_journeys.iter() {
+            stmt.execute((
+                &vj.id,
+                &vj.route_id,
+                &vj.physical_mode_id,
+                &vj.booking_rule_id,
+                vj.wheelchair_accessible.map(|a| a as u8),
+                vj.bikes_allowed.map(|a| a as u8),
+                vj.stroller_allowed.map(|a| a as u8),
+                vj.luggage_allowed.map(|a| a as u8),
+                &vj.vehicle_type_id,
+                &vj.on_demand_zone_id,
+            ))?;
+        }
+        let mut stmt = tx.prepare("INSERT INTO occupancies VALUES (?1, ?2, ?3, ?4)")?;
+        for (_, o) in pt_objects.occupancies.iter() {
+            stmt.execute((&o.id, &o.vehicle_journey_id, &o.stop_sequence, o.status as u8))?;
+        }
+        let mut stmt = tx.prepare("INSERT INTO object_codes VALUES (?1, ?2, ?3, ?4)")?;
+        for c in &pt_objects.object_codes {
+            stmt.execute((
+                object_type_name(c.object_type),
+                &c.object_id,
+                &c.object_system,
+                &c.object_code,
+            ))?;
+        }
+        let mut stmt = tx.prepare("INSERT INTO tickets VALUES (?1, ?2, ?3)")?;
+        for (_, t) in pt_objects.tickets.iter() {
+            stmt.execute((&t.id, &t.name, &t.comment))?;
+        }
+        let mut stmt = tx.prepare("INSERT INTO ticket_prices VALUES (?1, ?2, ?3, ?4, ?5)")?;
+        for p in &pt_objects.ticket_prices {
+            stmt.execute((
+                &p.ticket_id,
+                p.price,
+                &p.currency,
+                &p.validity_start,
+                &p.validity_end,
+            ))?;
+        }
+        let mut stmt = tx.prepare("INSERT INTO ticket_uses VALUES (?1, ?2, ?3, ?4, ?5)")?;
+        for (_, u) in pt_objects.ticket_uses.iter() {
+            stmt.execute((
+                &u.id,
+                &u.ticket_id,
+                &u.max_transfers,
+                &u.boarding_time_limit,
+                &u.alighting_time_limit,
+            ))?;
+        }
+        let mut stmt = tx.prepare("INSERT INTO ticket_use_perimeters VALUES (?1, ?2, ?3, ?4)")?;
+        for p in &pt_objects.ticket_use_perimeters {
+            stmt.execute((
+                &p.ticket_use_id,
+                object_type_name(p.object_type),
+                &p.object_id,
+                p.perimeter_action as u8,
+            ))?;
+        }
+    }
+    tx.commit()
+}
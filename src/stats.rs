@@ -0,0 +1,86 @@
+use std::fs;
+use std::path;
+use csv;
+use serde;
+use serde_json;
+
+use collection::Idx;
+use error::{csv_error, io_error, json_error, Result};
+use objects::*;
+use relations::{GetCorresponding, IdxSet};
+use PtObjects;
+
+#[derive(Serialize, Debug)]
+pub struct NetworkStats {
+    pub network_id: String,
+    pub nb_lines: usize,
+    pub nb_routes: usize,
+    pub nb_vehicle_journeys: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct LineStats {
+    pub network_id: String,
+    pub line_id: String,
+    pub nb_routes: usize,
+    pub nb_vehicle_journeys: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Report {
+    pub networks: Vec<NetworkStats>,
+    pub lines: Vec<LineStats>,
+}
+
+fn count<T, U>(idx: Idx<T>, pt_objects: &PtObjects) -> usize
+where
+    IdxSet<T>: GetCorresponding<U>,
+{
+    let from: IdxSet<T> = Some(idx).into_iter().collect();
+    let to: IdxSet<U> = from.get_corresponding(pt_objects);
+    to.len()
+}
+
+pub fn compute(pt_objects: &PtObjects) -> Report {
+    let networks = pt_objects
+        .networks
+        .iter()
+        .map(|(idx, network)| NetworkStats {
+            network_id: network.id.clone(),
+            nb_lines: count::<Network, Line>(idx, pt_objects),
+            nb_routes: count::<Network, Route>(idx, pt_objects),
+            nb_vehicle_journeys: count::<Network, VehicleJourney>(idx, pt_objects),
+        })
+        .collect();
+    let lines = pt_objects
+        .lines
+        .iter()
+        .map(|(idx, line)| LineStats {
+            network_id: line.network_id.clone(),
+            line_id: line.id.clone(),
+            nb_routes: count::<Line, Route>(idx, pt_objects),
+            nb_vehicle_journeys: count::<Line, VehicleJourney>(idx, pt_objects),
+        })
+        .collect();
+    Report { networks, lines }
+}
+
+fn write_stats<T: serde::Serialize>(path: &path::Path, stats: &[T]) -> Result<()> {
+    let mut wtr = csv::Writer::from_path(path).map_err(|e| csv_error(path, e))?;
+    for s in stats {
+        wtr.serialize(s).map_err(|e| csv_error(path, e))?;
+    }
+    wtr.flush().map_err(|e| io_error(path, e))
+}
+
+pub fn write_csv<P: AsRef<path::Path>>(path: P, report: &Report) -> Result<()> {
+    let path = path.as_ref();
+    write_stats(&path.join("network_stats.csv"), &report.networks)?;
+    write_stats(&path.join("line_stats.csv"), &report.lines)
+}
+
+pub fn write_json<P: AsRef<path::Path>>(path: P, report: &Report) -> Result<()> {
+    let path = path.as_ref();
+    let file = fs::File::create(path).map_err(|e| io_error(path, e))?;
+    serde_json::to_writer_pretty(file, report).map_err(|e| json_error(path, e))
+}